@@ -0,0 +1,351 @@
+use std::{collections::BTreeMap, io::Write};
+
+use csv::Writer;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use serde_json::{Value, json};
+
+use crate::{
+    clock::Instant,
+    currency::Currency,
+    engine::TransactionsEngine,
+    ids::{ClientId, TxId},
+};
+
+/// Number of seconds in a day
+const SECONDS_PER_DAY: u64 = 86_400;
+
+/// Unit the age of the disputes is counted in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AgeUnit {
+    /// Days, when the last transaction and every dispute have a timestamp
+    Days,
+    /// Transactions processed since the dispute has been opened
+    Rows,
+}
+
+/// Range of ages the open disputes are grouped by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AgeBucket {
+    /// Up to 30 days or rows
+    Recent,
+    /// From 31 to 60 days or rows
+    Aging,
+    /// 61 days or rows and more
+    Overdue,
+}
+
+impl AgeBucket {
+    pub const ALL: [Self; 3] = [Self::Recent, Self::Aging, Self::Overdue];
+
+    pub fn of(age: u64) -> Self {
+        match age {
+            0..=30 => Self::Recent,
+            31..=60 => Self::Aging,
+            _ => Self::Overdue,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Recent => "0-30",
+            Self::Aging => "31-60",
+            Self::Overdue => "61+",
+        }
+    }
+}
+
+/// Transaction currently disputed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpenDispute {
+    pub client: ClientId,
+    pub tx: TxId,
+    pub currency: Currency,
+    /// Amount of the disputed transaction
+    pub amount: Decimal,
+    /// Amount held by the dispute
+    pub held: Decimal,
+    /// When the dispute has been opened
+    pub opened_at: Instant,
+    /// Time since the dispute has been opened, in the unit of the report
+    pub age: u64,
+    /// Whether the client is locked
+    pub locked: bool,
+}
+
+/// Disputes still open, by age.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AgingReport {
+    /// When the report has been made
+    pub now: Instant,
+    pub unit: AgeUnit,
+    /// Open disputes, the oldest first
+    pub disputes: Vec<OpenDispute>,
+}
+
+impl AgingReport {
+    /// Open disputes of the clients of the engine, aged from the last processed transaction
+    pub fn new(engine: &TransactionsEngine) -> Self {
+        let now = engine.now();
+        let mut disputes: Vec<_> = engine
+            .clients()
+            .into_iter()
+            .flat_map(|client| {
+                client
+                    .open_disputes()
+                    .map(move |(tx, dispute)| OpenDispute {
+                        client: client.client,
+                        tx,
+                        currency: client.transaction_currency(tx),
+                        amount: dispute.amount(),
+                        held: dispute.held(),
+                        opened_at: dispute.opened_at().unwrap_or_default(),
+                        age: 0,
+                        locked: client.locked,
+                    })
+            })
+            .collect();
+
+        let unit = if now.timestamp.is_some()
+            && disputes
+                .iter()
+                .all(|dispute| dispute.opened_at.timestamp.is_some())
+        {
+            AgeUnit::Days
+        } else {
+            AgeUnit::Rows
+        };
+        for dispute in &mut disputes {
+            dispute.age = match unit {
+                AgeUnit::Days => {
+                    now.timestamp
+                        .unwrap_or_default()
+                        .saturating_sub(dispute.opened_at.timestamp.unwrap_or_default())
+                        / SECONDS_PER_DAY
+                }
+                AgeUnit::Rows => now.seq.saturating_sub(dispute.opened_at.seq),
+            };
+        }
+        disputes.sort_by_key(|dispute| (dispute.opened_at.seq, dispute.client, dispute.tx));
+
+        Self {
+            now,
+            unit,
+            disputes,
+        }
+    }
+
+    /// Number of open disputes and funds they hold, by bucket and currency
+    pub fn totals(&self) -> BTreeMap<(AgeBucket, Currency), (u64, Decimal)> {
+        let mut totals = BTreeMap::new();
+        for dispute in &self.disputes {
+            let (count, held) = totals
+                .entry((AgeBucket::of(dispute.age), dispute.currency.clone()))
+                .or_insert((0, Decimal::ZERO));
+            *count += 1;
+            *held += dispute.held;
+        }
+
+        totals
+    }
+
+    pub fn to_json(&self) -> Value {
+        let totals = self.totals();
+
+        json!({
+            "unit": self.unit,
+            "disputes": self.disputes.iter().map(|dispute| json!({
+                "client": dispute.client,
+                "tx": dispute.tx.to_string(),
+                "currency": dispute.currency.to_string(),
+                "amount": dispute.amount.to_string(),
+                "held": dispute.held.to_string(),
+                "opened_seq": dispute.opened_at.seq,
+                "opened_timestamp": dispute.opened_at.timestamp,
+                "age": dispute.age,
+                "bucket": AgeBucket::of(dispute.age).name(),
+                "locked": dispute.locked,
+            })).collect::<Vec<_>>(),
+            "buckets": AgeBucket::ALL.iter().map(|bucket| json!({
+                "bucket": bucket.name(),
+                "totals": totals
+                    .iter()
+                    .filter(|((total_bucket, _), _)| total_bucket == bucket)
+                    .map(|((_, currency), (count, held))| json!({
+                        "currency": currency.to_string(),
+                        "disputes": count,
+                        "held": held.to_string(),
+                    }))
+                    .collect::<Vec<_>>(),
+            })).collect::<Vec<_>>(),
+        })
+    }
+
+    /// Write the open disputes as CSV, then a blank line and the totals of each bucket, every
+    /// bucket being listed even when empty
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<(), String> {
+        let unit = match self.unit {
+            AgeUnit::Days => "age_days",
+            AgeUnit::Rows => "age_rows",
+        };
+
+        let mut disputes = Writer::from_writer(&mut writer);
+        disputes
+            .write_record([
+                "client",
+                "tx",
+                "currency",
+                "amount",
+                "held",
+                "opened_seq",
+                "opened_timestamp",
+                unit,
+                "bucket",
+                "locked",
+            ])
+            .map_err(|err| format!("Could not write record: {err:?}"))?;
+
+        for dispute in &self.disputes {
+            disputes
+                .write_record([
+                    dispute.client.to_string(),
+                    dispute.tx.to_string(),
+                    dispute.currency.to_string(),
+                    dispute.amount.to_string(),
+                    dispute.held.to_string(),
+                    dispute.opened_at.seq.to_string(),
+                    dispute
+                        .opened_at
+                        .timestamp
+                        .map(|timestamp| timestamp.to_string())
+                        .unwrap_or_default(),
+                    dispute.age.to_string(),
+                    AgeBucket::of(dispute.age).name().to_string(),
+                    dispute.locked.to_string(),
+                ])
+                .map_err(|err| format!("Could not write record: {err:?}"))?;
+        }
+
+        disputes
+            .flush()
+            .map_err(|err| format!("Could not flush records: {err:?}"))?;
+        drop(disputes);
+        writeln!(writer).map_err(|err| format!("Could not write report: {err:?}"))?;
+
+        let totals = self.totals();
+        let mut buckets = Writer::from_writer(writer);
+        buckets
+            .write_record(["bucket", "currency", "disputes", "held"])
+            .map_err(|err| format!("Could not write record: {err:?}"))?;
+
+        for bucket in AgeBucket::ALL {
+            let mut records: Vec<_> = totals
+                .iter()
+                .filter(|((total_bucket, _), _)| *total_bucket == bucket)
+                .map(|((_, currency), (count, held))| {
+                    [
+                        bucket.name().to_string(),
+                        currency.to_string(),
+                        count.to_string(),
+                        held.to_string(),
+                    ]
+                })
+                .collect();
+            if records.is_empty() {
+                records.push([
+                    bucket.name().to_string(),
+                    String::new(),
+                    "0".to_string(),
+                    "0".to_string(),
+                ]);
+            }
+
+            for record in records {
+                buckets
+                    .write_record(record)
+                    .map_err(|err| format!("Could not write record: {err:?}"))?;
+            }
+        }
+
+        buckets
+            .flush()
+            .map_err(|err| format!("Could not flush records: {err:?}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+
+    use crate::{
+        engine::TransactionsEngine,
+        ids::{ClientId, TxId},
+        types::TransactionType,
+    };
+
+    use super::{AgeBucket, AgeUnit, AgingReport};
+
+    #[test]
+    fn test_aging() {
+        let day = 86_400;
+        let mut engine = TransactionsEngine::new();
+        let deposit = |client, tx, amount| TransactionType::Deposit {
+            client: ClientId(client),
+            tx: TxId::from(tx),
+            amount: Decimal::new(amount, 0),
+            currency: None,
+        };
+        let dispute = |client, tx| TransactionType::Dispute {
+            client: ClientId(client),
+            tx: TxId::from(tx),
+            amount: None,
+        };
+        for (transaction, timestamp) in [
+            (deposit(1, 1, 10), 0),
+            (deposit(1, 2, 5), 0),
+            (deposit(2, 3, 7), 0),
+            (dispute(1, 1), day),
+            (dispute(2, 3), 40 * day),
+            (dispute(1, 2), 60 * day),
+            (
+                TransactionType::Resolve {
+                    client: ClientId(1),
+                    tx: TxId::from(2),
+                    amount: None,
+                },
+                70 * day,
+            ),
+            (deposit(3, 4, 1), 80 * day),
+        ] {
+            engine
+                .process_transaction_at(transaction, Some(timestamp))
+                .expect("Could not process transaction.");
+        }
+
+        let report = AgingReport::new(&engine);
+        assert_eq!(report.unit, AgeUnit::Days);
+        let ages: Vec<_> = report
+            .disputes
+            .iter()
+            .map(|dispute| (dispute.tx, dispute.age))
+            .collect();
+        assert_eq!(ages, [(TxId::from(1), 79), (TxId::from(3), 40)]);
+        assert_eq!(AgeBucket::of(30), AgeBucket::Recent);
+        assert_eq!(AgeBucket::of(61), AgeBucket::Overdue);
+
+        let mut output = Vec::new();
+        report.write(&mut output).expect("Could not write report.");
+        assert_eq!(
+            String::from_utf8(output).expect("Invalid output."),
+            "client,tx,currency,amount,held,opened_seq,opened_timestamp,age_days,bucket,locked\n\
+             1,1,,10,10,4,86400,79,61+,false\n\
+             2,3,,7,7,5,3456000,40,31-60,false\n\
+             \n\
+             bucket,currency,disputes,held\n\
+             0-30,,0,0\n\
+             31-60,,1,7\n\
+             61+,,1,10\n"
+        );
+    }
+}
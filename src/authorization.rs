@@ -0,0 +1,164 @@
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    clock::{Instant, Window},
+    error::EngineError,
+};
+
+/// State of an authorization.
+///
+/// An authorization starts as `Pending` while funds are reserved. It is `Captured` once the whole
+/// reservation has been settled, or it is closed by a `Void` or when it `Expired`, releasing what
+/// is still reserved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AuthorizationState {
+    Pending,
+    Captured,
+    Voided,
+    Expired,
+}
+
+/// Rules applied to authorizations.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuthorizationPolicy {
+    /// Period after which an authorization that is not fully captured expires, `None` to keep the
+    /// funds reserved until a capture or a void
+    pub expiry: Option<Window>,
+}
+
+impl Default for AuthorizationPolicy {
+    fn default() -> Self {
+        Self {
+            expiry: Some(Window::Days(7)),
+        }
+    }
+}
+
+/// Funds reserved by an authorization, waiting to be captured.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Authorization {
+    /// Current state
+    state: AuthorizationState,
+    /// Amount still reserved
+    reserved: Decimal,
+    /// Amount that has been captured
+    captured: Decimal,
+    /// When the authorization has been made
+    authorized_at: Instant,
+}
+
+impl Authorization {
+    pub fn new(amount: Decimal, authorized_at: Instant) -> Self {
+        Self {
+            state: AuthorizationState::Pending,
+            reserved: amount,
+            captured: Decimal::ZERO,
+            authorized_at,
+        }
+    }
+
+    pub fn state(&self) -> AuthorizationState {
+        self.state
+    }
+
+    /// Amount still reserved
+    pub fn reserved(&self) -> Decimal {
+        self.reserved
+    }
+
+    /// Amount that has been captured
+    pub fn captured(&self) -> Decimal {
+        self.captured
+    }
+
+    pub fn authorized_at(&self) -> Instant {
+        self.authorized_at
+    }
+
+    /// Settle the given amount, or everything that is reserved. The rest stays reserved for later
+    /// captures. Returns the captured amount.
+    pub fn capture(&mut self, amount: Option<Decimal>) -> Result<Decimal, EngineError> {
+        self.check_pending()?;
+
+        let amount = amount.unwrap_or(self.reserved);
+        if amount <= Decimal::ZERO {
+            return Err(EngineError::NonPositiveAmount);
+        }
+        if amount > self.reserved {
+            return Err(EngineError::ExceedsReserved);
+        }
+
+        self.reserved -= amount;
+        self.captured += amount;
+        if self.reserved.is_zero() {
+            self.state = AuthorizationState::Captured;
+        }
+
+        Ok(amount)
+    }
+
+    /// Release everything that is reserved. Returns the released amount.
+    pub fn void(&mut self) -> Result<Decimal, EngineError> {
+        self.close(AuthorizationState::Voided)
+    }
+
+    /// Release everything that is reserved because the authorization has not been captured in
+    /// time. Returns the released amount.
+    pub fn expire(&mut self) -> Result<Decimal, EngineError> {
+        self.close(AuthorizationState::Expired)
+    }
+
+    fn close(&mut self, state: AuthorizationState) -> Result<Decimal, EngineError> {
+        self.check_pending()?;
+
+        let released = self.reserved;
+        self.reserved = Decimal::ZERO;
+        self.state = state;
+
+        Ok(released)
+    }
+
+    fn check_pending(&self) -> Result<(), EngineError> {
+        if self.state != AuthorizationState::Pending {
+            return Err(EngineError::AuthorizationNotPending);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+
+    use crate::clock::Instant;
+
+    use super::{Authorization, AuthorizationState};
+
+    #[test]
+    fn test_authorization_lifecycle() {
+        let mut authorization = Authorization::new(Decimal::new(10, 0), Instant::default());
+
+        assert!(authorization.capture(Some(Decimal::new(11, 0))).is_err());
+        assert!(authorization.capture(Some(Decimal::ZERO)).is_err());
+
+        // a partial capture keeps the rest reserved
+        assert_eq!(
+            authorization.capture(Some(Decimal::new(4, 0))),
+            Ok(Decimal::new(4, 0))
+        );
+        assert_eq!(authorization.state(), AuthorizationState::Pending);
+        assert_eq!(authorization.reserved(), Decimal::new(6, 0));
+
+        assert_eq!(authorization.void(), Ok(Decimal::new(6, 0)));
+        assert_eq!(authorization.state(), AuthorizationState::Voided);
+        assert_eq!(authorization.captured(), Decimal::new(4, 0));
+
+        // a closed authorization can't be captured, voided or expired
+        assert!(authorization.capture(None).is_err());
+        assert!(authorization.void().is_err());
+        assert!(authorization.expire().is_err());
+    }
+}
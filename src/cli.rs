@@ -0,0 +1,291 @@
+use std::{ffi::OsString, fs::File};
+
+use clap::{Args, Parser, Subcommand};
+use rust_decimal::Decimal;
+use serde_json::{Value, json};
+use transactions_engine::{
+    config::EngineConfig,
+    ids::{ClientId, TxId},
+    socket::ListenAddress,
+};
+
+/// Names of the subcommands, any other first argument is the input file of `process`
+const COMMANDS: [&str; 12] = [
+    "process",
+    "validate",
+    "replay",
+    "serve",
+    "listen",
+    "repl",
+    "diff",
+    "reconcile",
+    "statement",
+    "disputes",
+    "stats",
+    "help",
+];
+
+/// Process client transactions and report the state of their accounts.
+#[derive(Debug, Parser)]
+#[command(version)]
+pub struct Cli {
+    #[command(flatten)]
+    pub settings: Settings,
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Process a CSV file of transactions and write the state of the clients
+    Process { input: String },
+    /// Simulate a CSV file of transactions without keeping any state, reporting the rows that
+    /// would be rejected and the projected changes of the accounts
+    Validate {
+        input: String,
+        /// Snapshot to simulate the transactions on, instead of an empty engine
+        #[arg(long)]
+        snapshot: Option<String>,
+    },
+    /// Restore a snapshot, process a CSV file of transactions on top of it and write the state of
+    /// the clients
+    Replay { snapshot: String, input: String },
+    /// Serve the engine over HTTP
+    Serve {
+        #[arg(default_value = "127.0.0.1:8080")]
+        address: String,
+    },
+    /// Ingest newline-delimited records from TCP or Unix socket connections
+    Listen {
+        /// `tcp:<host>:<port>` or `unix:<path>`
+        address: ListenAddress,
+    },
+    /// Start an interactive session
+    Repl {
+        /// Snapshot to start from
+        #[arg(long)]
+        snapshot: Option<String>,
+    },
+    /// Compare two outputs of the engine or two snapshots, `.json` files being snapshots
+    Diff {
+        before: String,
+        after: String,
+        /// List the transactions responsible for the changes, only between snapshots
+        #[arg(long)]
+        transactions: bool,
+    },
+    /// Reconcile an output of the engine, or a snapshot when it is a `.json` file, against a CSV
+    /// file of expected balances
+    Reconcile {
+        state: String,
+        expected: String,
+        /// Largest difference between the expected and the actual total funds that is not a break
+        #[arg(long)]
+        tolerance: Option<Decimal>,
+        /// Currency of the expected balances without one
+        #[arg(long)]
+        currency: Option<String>,
+    },
+    /// Statement of a client over a CSV file of transactions, with the opening and closing funds
+    /// and every operation in between
+    Statement {
+        input: String,
+        #[arg(long)]
+        client: ClientId,
+        /// First transaction of the client in the statement
+        #[arg(long)]
+        from_tx: Option<TxId>,
+        /// Last transaction of the client in the statement
+        #[arg(long)]
+        to_tx: Option<TxId>,
+        /// Earliest timestamp of the statement, rows without a timestamp are left out when set
+        #[arg(long)]
+        from: Option<u64>,
+        /// Latest timestamp of the statement
+        #[arg(long)]
+        to: Option<u64>,
+        /// Snapshot to process the transactions on, instead of an empty engine
+        #[arg(long)]
+        snapshot: Option<String>,
+    },
+    /// Open disputes by age, after processing a CSV file of transactions or restoring a snapshot
+    Disputes {
+        input: Option<String>,
+        /// Snapshot to start from
+        #[arg(long)]
+        snapshot: Option<String>,
+    },
+    /// Summary of the transactions of a CSV file and of the resulting state of the clients
+    Stats { input: String },
+}
+
+/// Settings of the engine, from the lowest to the highest precedence: environment variables,
+/// configuration file and flags.
+#[derive(Debug, Default, Args)]
+pub struct Settings {
+    /// JSON configuration file
+    #[arg(long, short, global = true, env = "ENGINE_CONFIG")]
+    pub config: Option<String>,
+    /// Format of the state of the clients [env: ENGINE_FORMAT]
+    #[arg(long, global = true, value_parser = ["csv", "json", "markdown"])]
+    pub format: Option<String>,
+    /// Order of the clients in the output [env: ENGINE_ORDER]
+    #[arg(long, global = true, value_parser = ["none", "client"])]
+    pub order: Option<String>,
+    /// Maximum number of times the same transaction can be disputed [env: ENGINE_MAX_DISPUTES]
+    #[arg(long, global = true)]
+    pub max_disputes: Option<u32>,
+    /// Reject or only flag the transactions breaching a limit [env: ENGINE_LIMIT_MODE]
+    #[arg(long, global = true, value_parser = ["hard", "soft"])]
+    pub limit_mode: Option<String>,
+    /// Currency of the transactions without one [env: ENGINE_DEFAULT_CURRENCY]
+    #[arg(long, global = true)]
+    pub default_currency: Option<String>,
+    /// Reject the transactions of the clients that are not registered
+    /// [env: ENGINE_REJECT_UNREGISTERED]
+    #[arg(long, global = true)]
+    pub reject_unregistered: bool,
+}
+
+impl Settings {
+    /// Settings read from the environment variables
+    pub fn from_env(var: impl Fn(&str) -> Option<String>) -> Result<Self, String> {
+        let max_disputes = var("ENGINE_MAX_DISPUTES")
+            .map(|max| {
+                max.parse()
+                    .map_err(|err| format!("Invalid ENGINE_MAX_DISPUTES {max:?}: {err}"))
+            })
+            .transpose()?;
+        let reject_unregistered = var("ENGINE_REJECT_UNREGISTERED")
+            .map(|reject| {
+                reject
+                    .parse()
+                    .map_err(|err| format!("Invalid ENGINE_REJECT_UNREGISTERED {reject:?}: {err}"))
+            })
+            .transpose()?
+            .unwrap_or_default();
+
+        Ok(Self {
+            config: None,
+            format: var("ENGINE_FORMAT"),
+            order: var("ENGINE_ORDER"),
+            max_disputes,
+            limit_mode: var("ENGINE_LIMIT_MODE"),
+            default_currency: var("ENGINE_DEFAULT_CURRENCY"),
+            reject_unregistered,
+        })
+    }
+
+    /// Configuration of the engine, the settings read from the environment variables are
+    /// overridden by the configuration file, itself overridden by these settings
+    pub fn engine_config(&self, env: &Settings) -> Result<EngineConfig, String> {
+        let file = match &self.config {
+            Some(path) => {
+                let file = File::open(path)
+                    .map_err(|err| format!("Could not open config file {path}: {err}"))?;
+                serde_json::from_reader(file)
+                    .map_err(|err| format!("Invalid config file {path}: {err}"))?
+            }
+            None => json!({}),
+        };
+
+        EngineConfig::from_layers([env.to_layer(), file, self.to_layer()])
+    }
+
+    /// Settings in the format of the configuration file, only with the ones that are set
+    fn to_layer(&self) -> Value {
+        let mut layer = json!({});
+        if let Some(format) = &self.format {
+            layer["output_format"] = json!(format);
+        }
+        if let Some(order) = &self.order {
+            layer["output_order"] = json!(order);
+        }
+        if let Some(max_disputes) = self.max_disputes {
+            layer["dispute"] = json!({ "max_disputes": max_disputes });
+        }
+        if let Some(mode) = &self.limit_mode {
+            layer["limits"] = json!({ "mode": mode });
+        }
+        if let Some(currency) = &self.default_currency {
+            layer["default_currency"] = json!(currency);
+        }
+        if self.reject_unregistered {
+            layer["registry"] = json!({ "reject_unregistered": true });
+        }
+
+        layer
+    }
+}
+
+/// Arguments of the previous command line, `<input> [config]`, as the `process` subcommand
+pub fn normalize_args(args: Vec<OsString>) -> Vec<OsString> {
+    let Some(first) = args.get(1).and_then(|arg| arg.to_str()) else {
+        return args;
+    };
+    if first.starts_with('-') || COMMANDS.contains(&first) {
+        return args;
+    }
+
+    let mut normalized = vec![args[0].clone(), "process".into(), args[1].clone()];
+    if let Some(config) = args.get(2) {
+        normalized.extend(["--config".into(), config.clone()]);
+    }
+    normalized.extend(args.into_iter().skip(3));
+
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use clap::Parser;
+    use transactions_engine::{limits::LimitMode, output::OutputFormat};
+
+    use super::{Cli, Command, Settings, normalize_args};
+
+    #[test]
+    fn test_cli() {
+        let args = normalize_args(
+            ["engine", "input.csv", "config.json"]
+                .map(Into::into)
+                .to_vec(),
+        );
+        let cli = Cli::try_parse_from(args).expect("Could not parse arguments.");
+        assert_eq!(cli.settings.config.as_deref(), Some("config.json"));
+        assert!(matches!(cli.command, Command::Process { input } if input == "input.csv"));
+
+        let cli = Cli::try_parse_from(["engine", "stats", "input.csv", "--format", "json"])
+            .expect("Could not parse arguments.");
+        assert_eq!(cli.settings.format.as_deref(), Some("json"));
+        assert!(Cli::try_parse_from(["engine", "stats", "input.csv", "--format", "xml"]).is_err());
+
+        // environment < configuration file < flags
+        let vars = HashMap::from([
+            ("ENGINE_FORMAT", "json"),
+            ("ENGINE_MAX_DISPUTES", "3"),
+            ("ENGINE_LIMIT_MODE", "soft"),
+        ]);
+        let env = Settings::from_env(|name| vars.get(name).map(|value| value.to_string()))
+            .expect("Invalid environment.");
+        let path = std::env::temp_dir().join("cli_test_config.json");
+        std::fs::write(
+            &path,
+            r#"{"dispute": {"max_disputes": 2}, "limits": {"mode": "hard"}}"#,
+        )
+        .expect("Could not write config.");
+        let flags = Settings {
+            config: Some(path.to_string_lossy().to_string()),
+            limit_mode: Some("soft".to_string()),
+            ..Default::default()
+        };
+        let config = flags.engine_config(&env).expect("Invalid config.");
+        assert_eq!(config.output_format, OutputFormat::Json);
+        assert_eq!(config.dispute.max_disputes, Some(2));
+        assert_eq!(config.limits.mode, LimitMode::Soft);
+
+        let vars = HashMap::from([("ENGINE_MAX_DISPUTES", "many")]);
+        assert!(Settings::from_env(|name| vars.get(name).map(|value| value.to_string())).is_err());
+    }
+}
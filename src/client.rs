@@ -1,279 +1,716 @@
-use std::collections::{HashMap, HashSet};
-
-use crate::types::{DECIMAL_PRECISION, TransactionType};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{
+    authorization::Authorization,
+    clock::Instant,
+    currency::{Balance, Currency},
+    dispute::{DisputePolicy, DisputeState, TransactionDispute},
+    error::EngineError,
+    ids::{ClientId, TxId},
+    interest::postable,
+    limits::Activity,
+    types::{DECIMAL_PRECISION, TransactionType},
+};
 use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
 use tracing::info;
 
 /// Type containing all the information needed for a client account
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Client {
     /// Client ID
-    pub client: u16,
-    /// Available amount
-    pub available: Decimal,
-    /// Amount under dispute
-    pub held: Decimal,
-    /// Total funds
-    pub total: Decimal,
+    pub client: ClientId,
+    /// Funds in each currency
+    pub balances: BTreeMap<Currency, Balance>,
     /// Account state
     pub locked: bool,
+    /// Amount the available funds can go below zero, in each currency
+    pub credit_limit: Decimal,
+    /// Currency of the transactions without one, instead of the default currency of the engine
+    pub currency: Option<Currency>,
     /// Transactions processed by the engine
-    pub processed_transactions: HashMap<u32, TransactionType>,
-    /// Transaction ids that are under dispute
-    disputed_transactions: HashSet<u32>,
+    pub processed_transactions: HashMap<TxId, TransactionType>,
+    /// When the transactions have been processed
+    processed_at: HashMap<TxId, Instant>,
+    /// Dispute lifecycle of the transactions that have been disputed or refunded
+    disputes: HashMap<TxId, TransactionDispute>,
+    /// Authorizations made by the client
+    authorizations: HashMap<TxId, Authorization>,
+    /// Deposits, withdrawals and transfers, used by the velocity controls
+    activity: Activity,
 }
 
 impl Client {
-    pub fn new(client: u16) -> Self {
+    pub fn new(client: ClientId) -> Self {
         Self {
             client,
             ..Default::default()
         }
     }
 
-    fn check_if_locked(&self) -> Result<(), String> {
+    fn check_if_locked(&self) -> Result<(), EngineError> {
         if self.locked {
-            return Err("Account is locked, cannot process transaction".to_string());
+            return Err(EngineError::AccountLocked);
         }
 
         Ok(())
     }
 
-    /// Handle deposit for current client
-    pub fn deposit(&mut self, amount: Decimal, tx: u32) -> Result<(), String> {
-        info!(
-            "Deposit - client {}, tx {}, amount {}",
-            self.client, tx, amount
-        );
+    /// Funds in the given currency
+    pub fn balance(&self, currency: &Currency) -> Balance {
+        self.balances.get(currency).cloned().unwrap_or_default()
+    }
+
+    fn balance_mut(&mut self, currency: &Currency) -> &mut Balance {
+        self.balances.entry(currency.clone()).or_default()
+    }
+
+    /// Currency of the given transaction id
+    pub fn transaction_currency(&self, tx: TxId) -> Currency {
+        self.processed_transactions
+            .get(&tx)
+            .and_then(TransactionType::funds)
+            .and_then(|(_, currency)| currency.cloned())
+            .unwrap_or_default()
+    }
+
+    /// Record a transaction processed by the engine
+    pub fn record_transaction(&mut self, tx: TxId, transaction: TransactionType, at: Instant) {
+        self.activity.record(self.client, &transaction, at);
+        self.processed_transactions.insert(tx, transaction);
+        self.processed_at.insert(tx, at);
+    }
+
+    /// Deposits, withdrawals and transfers processed for current client
+    pub fn activity(&self) -> &Activity {
+        &self.activity
+    }
+
+    /// When the given transaction id has been processed, if it is known
+    pub fn processed_at(&self, tx: TxId) -> Option<Instant> {
+        self.processed_at.get(&tx).copied()
+    }
 
+    /// Dispute state of the given transaction id, if it has been processed
+    pub fn dispute_state(&self, tx: TxId) -> Option<DisputeState> {
+        if !self.processed_transactions.contains_key(&tx) {
+            return None;
+        }
+
+        Some(
+            self.disputes
+                .get(&tx)
+                .map_or(DisputeState::Settled, TransactionDispute::state),
+        )
+    }
+
+    /// Dispute states the given transaction id went through, if it has been processed
+    pub fn dispute_history(&self, tx: TxId) -> Option<&[DisputeState]> {
+        if !self.processed_transactions.contains_key(&tx) {
+            return None;
+        }
+
+        Some(
+            self.disputes
+                .get(&tx)
+                .map_or(&[DisputeState::Settled], TransactionDispute::history),
+        )
+    }
+
+    /// Disputes of the transactions that are currently disputed
+    pub fn open_disputes(&self) -> impl Iterator<Item = (TxId, &TransactionDispute)> {
+        self.disputes
+            .iter()
+            .filter(|(_, dispute)| dispute.state() == DisputeState::Disputed)
+            .map(|(tx, dispute)| (*tx, dispute))
+    }
+
+    /// Check that the given amount can be deposited for current client and that the funds cover
+    /// the fee of the deposit
+    pub fn check_deposit(
+        &self,
+        amount: Decimal,
+        tx: TxId,
+        fee: Decimal,
+        currency: &Currency,
+    ) -> Result<(), EngineError> {
         self.check_if_locked()?;
 
         if self.processed_transactions.contains_key(&tx) {
-            return Err("Transaction already processed".to_string());
+            return Err(EngineError::DuplicateTransaction);
         }
 
         if amount < Decimal::new(0, DECIMAL_PRECISION) {
-            return Err("Negative amount".to_string());
+            return Err(EngineError::NegativeAmount);
+        }
+
+        if self.balance(currency).available + amount < fee {
+            return Err(EngineError::InsufficientFundsForFee);
         }
 
-        self.available += amount;
-        self.total += amount;
+        Ok(())
+    }
+
+    /// Handle deposit for current client
+    pub fn deposit(
+        &mut self,
+        amount: Decimal,
+        tx: TxId,
+        currency: &Currency,
+    ) -> Result<(), EngineError> {
+        info!(
+            "Deposit - client {}, tx {}, amount {} {}",
+            self.client, tx, amount, currency
+        );
+
+        self.check_deposit(amount, tx, Decimal::ZERO, currency)?;
+
+        let balance = self.balance_mut(currency);
+        balance.available += amount;
+        balance.total += amount;
 
         Ok(())
     }
 
     /// Handle withdrawal for current client
-    pub fn withdrawal(&mut self, amount: Decimal, tx: u32) -> Result<(), String> {
+    pub fn withdrawal(
+        &mut self,
+        amount: Decimal,
+        tx: TxId,
+        currency: &Currency,
+    ) -> Result<(), EngineError> {
         info!(
-            "Withdrawal - client {}, tx {}, amount {}",
-            self.client, tx, amount
+            "Withdrawal - client {}, tx {}, amount {} {}",
+            self.client, tx, amount, currency
         );
 
+        self.check_withdrawal(amount, tx, Decimal::ZERO, currency)?;
+
+        let balance = self.balance_mut(currency);
+        balance.available -= amount;
+        balance.total -= amount;
+
+        Ok(())
+    }
+
+    /// Check that the given amount can be withdrawn by current client together with the fee of
+    /// the withdrawal, drawing on the credit line if needed
+    pub fn check_withdrawal(
+        &self,
+        amount: Decimal,
+        tx: TxId,
+        fee: Decimal,
+        currency: &Currency,
+    ) -> Result<(), EngineError> {
         self.check_if_locked()?;
 
         if self.processed_transactions.contains_key(&tx) {
-            return Err("Transaction already processed".to_string());
+            return Err(EngineError::DuplicateTransaction);
         }
 
         if amount < Decimal::new(0, DECIMAL_PRECISION) {
-            return Err("Negative amount".to_string());
+            return Err(EngineError::NegativeAmount);
         }
 
-        if self.available < amount {
-            return Err("Insufficient funds".to_string());
+        let available = self.balance(currency).available + self.credit_limit;
+
+        if available < amount {
+            return Err(EngineError::InsufficientFunds);
         }
 
-        self.available -= amount;
-        self.total -= amount;
+        if available < amount + fee {
+            return Err(EngineError::InsufficientFundsForFee);
+        }
 
         Ok(())
     }
 
-    /// Handle dispute for current client and given transaction id
-    pub fn dispute(&mut self, tx: u32) -> Result<(), String> {
-        info!("Dispute - client {}, tx {}", self.client, tx);
+    /// Convert funds of current client: `amount` in `from` is exchanged for `credited` in `to`
+    pub fn convert(
+        &mut self,
+        tx: TxId,
+        amount: Decimal,
+        from: &Currency,
+        credited: Decimal,
+        to: &Currency,
+    ) -> Result<(), EngineError> {
+        info!(
+            "Convert - client {}, tx {}, amount {} {}, credited {} {}",
+            self.client, tx, amount, from, credited, to
+        );
+
+        self.check_withdrawal(amount, tx, Decimal::ZERO, from)?;
+
+        let balance = self.balance_mut(from);
+        balance.available -= amount;
+        balance.total -= amount;
+
+        let balance = self.balance_mut(to);
+        balance.available += credited;
+        balance.total += credited;
+
+        Ok(())
+    }
+
+    /// Reserve the given amount for current client until it is captured or released
+    pub fn authorize(
+        &mut self,
+        amount: Decimal,
+        tx: TxId,
+        currency: &Currency,
+        now: Instant,
+    ) -> Result<(), EngineError> {
+        info!(
+            "Authorize - client {}, tx {}, amount {} {}",
+            self.client, tx, amount, currency
+        );
+
+        self.check_withdrawal(amount, tx, Decimal::ZERO, currency)?;
+
+        let balance = self.balance_mut(currency);
+        balance.available -= amount;
+        balance.reserved += amount;
+
+        self.authorizations
+            .insert(tx, Authorization::new(amount, now));
+
+        Ok(())
+    }
+
+    /// Capture the given authorization id. Only the given amount is settled if one is provided,
+    /// otherwise everything that is reserved.
+    pub fn capture(&mut self, tx: TxId, amount: Option<Decimal>) -> Result<(), EngineError> {
+        info!(
+            "Capture - client {}, tx {}, amount {:?}",
+            self.client, tx, amount
+        );
 
         self.check_if_locked()?;
 
-        if self.disputed_transactions.contains(&tx) {
-            return Err("Transaction already disputed".to_string());
-        }
+        let captured = self
+            .authorizations
+            .get_mut(&tx)
+            .ok_or(EngineError::AuthorizationNotFound)?
+            .capture(amount)?;
 
-        let transaction = self
-            .processed_transactions
-            .get(&tx)
-            .ok_or_else(|| "Transaction id not found in processed transactions".to_string())?;
+        let currency = self.transaction_currency(tx);
+        let balance = self.balance_mut(&currency);
+        balance.reserved -= captured;
+        balance.total -= captured;
 
-        if let TransactionType::Deposit {
-            client: _,
-            tx,
-            amount,
-        } = transaction
-        {
-            self.available -= amount;
-            self.held += amount;
+        Ok(())
+    }
 
-            self.disputed_transactions.insert(*tx);
-        }
+    /// Release the funds reserved by the given authorization id
+    pub fn void(&mut self, tx: TxId) -> Result<(), EngineError> {
+        info!("Void - client {}, tx {}", self.client, tx);
+
+        self.check_if_locked()?;
+
+        let released = self
+            .authorizations
+            .get_mut(&tx)
+            .ok_or(EngineError::AuthorizationNotFound)?
+            .void()?;
+
+        self.release_reservation(tx, released);
 
         Ok(())
     }
 
-    /// Resolve the given transaction id that is under dispute
-    pub fn resolve(&mut self, tx: u32) -> Result<(), String> {
-        info!("Resolve - client {}, tx {}", self.client, tx);
+    /// Release the funds reserved by the given authorization id because it has not been captured
+    /// in time. Expired authorizations are released even if the account is locked. Returns the
+    /// released amount.
+    pub fn expire_authorization(&mut self, tx: TxId) -> Result<Decimal, EngineError> {
+        info!("Authorization expired - client {}, tx {}", self.client, tx);
+
+        let released = self
+            .authorizations
+            .get_mut(&tx)
+            .ok_or(EngineError::AuthorizationNotFound)?
+            .expire()?;
+
+        self.release_reservation(tx, released);
+
+        Ok(released)
+    }
+
+    fn release_reservation(&mut self, tx: TxId, released: Decimal) {
+        let currency = self.transaction_currency(tx);
+        let balance = self.balance_mut(&currency);
+        balance.reserved -= released;
+        balance.available += released;
+    }
+
+    /// Authorization of the given transaction id, if it is an authorization
+    pub fn authorization(&self, tx: TxId) -> Option<&Authorization> {
+        self.authorizations.get(&tx)
+    }
+
+    /// Charge a fee for the given transaction id. The fee is charged even if the account is
+    /// locked or the available funds don't cover it.
+    pub fn charge_fee(&mut self, fee: Decimal, tx: TxId, currency: &Currency) {
+        info!(
+            "Fee - client {}, tx {}, amount {} {}",
+            self.client, tx, fee, currency
+        );
+
+        let balance = self.balance_mut(currency);
+        balance.available -= fee;
+        balance.total -= fee;
+        balance.fees += fee;
+    }
+
+    /// Handle dispute for current client and given transaction id. Only the given amount is
+    /// held if one is provided, otherwise everything that is not disputed yet.
+    pub fn dispute(
+        &mut self,
+        tx: TxId,
+        amount: Option<Decimal>,
+        policy: &DisputePolicy,
+        now: Instant,
+    ) -> Result<(), EngineError> {
+        info!(
+            "Dispute - client {}, tx {}, amount {:?}",
+            self.client, tx, amount
+        );
 
         self.check_if_locked()?;
 
         let transaction = self
             .processed_transactions
             .get(&tx)
-            .ok_or_else(|| "Transaction id not found in processed transactions".to_string())?;
+            .ok_or(EngineError::TransactionNotFound)?;
 
-        if !self.disputed_transactions.remove(&tx) {
-            return Err("Transaction id has not been disputed".to_string())?;
+        if let (Some(window), Some(processed_at)) = (policy.window, self.processed_at(tx))
+            && window.has_elapsed(processed_at, now)
+        {
+            return Err(EngineError::DisputeWindowExpired);
         }
 
-        if let TransactionType::Deposit {
-            client: _,
-            tx: _,
-            amount,
-        } = transaction
-        {
-            self.held -= amount;
-            self.available += amount;
+        // Funds can only be held by the client that received them
+        let received = match transaction {
+            TransactionType::Deposit { amount, .. } => Some(*amount),
+            TransactionType::Transfer {
+                destination,
+                amount,
+                ..
+            } if *destination == self.client => Some(*amount),
+            TransactionType::Transfer { .. } => {
+                return Err(EngineError::TransferDisputedBySource);
+            }
+            _ => None,
+        };
+
+        if let Some(received) = received {
+            let held = self
+                .disputes
+                .entry(tx)
+                .or_insert_with(|| TransactionDispute::new(received))
+                .open(amount, policy, now)?;
+
+            let currency = self.transaction_currency(tx);
+            let balance = self.balance_mut(&currency);
+            balance.available -= held;
+            balance.held += held;
+        }
+
+        Ok(())
+    }
+
+    /// Resolve the given transaction id that is under dispute. Only the given amount is released
+    /// if one is provided, otherwise everything that is held.
+    pub fn resolve(&mut self, tx: TxId, amount: Option<Decimal>) -> Result<(), EngineError> {
+        info!(
+            "Resolve - client {}, tx {}, amount {:?}",
+            self.client, tx, amount
+        );
+
+        self.check_if_locked()?;
+
+        if !self.processed_transactions.contains_key(&tx) {
+            return Err(EngineError::TransactionNotFound);
         }
 
+        let released = self
+            .disputes
+            .get_mut(&tx)
+            .ok_or(EngineError::NotDisputed)?
+            .resolve(amount)?;
+
+        let currency = self.transaction_currency(tx);
+        let balance = self.balance_mut(&currency);
+        balance.held -= released;
+        balance.available += released;
+
         Ok(())
     }
 
-    /// Performs chargeback for given transaction and locks the account
-    pub fn chargeback(&mut self, tx: u32) -> Result<(), String> {
-        info!("Chargeback - client {}, tx {}", self.client, tx);
+    /// Performs chargeback for given transaction and locks the account. Only the given amount is
+    /// charged back if one is provided, the rest of the held amount is released. Returns the
+    /// charged back amount.
+    pub fn chargeback(
+        &mut self,
+        tx: TxId,
+        amount: Option<Decimal>,
+    ) -> Result<Decimal, EngineError> {
+        info!(
+            "Chargeback - client {}, tx {}, amount {:?}",
+            self.client, tx, amount
+        );
+
+        self.check_if_locked()?;
+
+        if !self.processed_transactions.contains_key(&tx) {
+            return Err(EngineError::TransactionNotFound);
+        }
+
+        let dispute = self.disputes.get_mut(&tx).ok_or(EngineError::NotDisputed)?;
+
+        let held = dispute.held();
+        let charged_back = dispute.chargeback(amount)?;
+
+        let currency = self.transaction_currency(tx);
+        let balance = self.balance_mut(&currency);
+        balance.held -= held;
+        balance.available += held - charged_back;
+        balance.total -= charged_back;
+        self.locked = true;
+
+        Ok(charged_back)
+    }
+
+    /// Refund the given deposit or withdrawal id without locking the account. Only the given
+    /// amount is refunded if one is provided, otherwise everything that has not been disputed or
+    /// refunded yet. A deposit is refunded from the available funds, a withdrawal is credited back.
+    pub fn refund(&mut self, tx: TxId, amount: Option<Decimal>) -> Result<(), EngineError> {
+        info!(
+            "Refund - client {}, tx {}, amount {:?}",
+            self.client, tx, amount
+        );
 
         self.check_if_locked()?;
 
         let transaction = self
             .processed_transactions
             .get(&tx)
-            .ok_or_else(|| "Transaction id not found in processed transactions".to_string())?;
+            .ok_or(EngineError::TransactionNotFound)?;
 
-        if !self.disputed_transactions.remove(&tx) {
-            return Err("Transaction id has not been disputed".to_string())?;
-        }
+        let (original, sign) = match transaction {
+            TransactionType::Deposit { amount, .. } => (*amount, Decimal::NEGATIVE_ONE),
+            TransactionType::Withdrawal { amount, .. } => (*amount, Decimal::ONE),
+            _ => return Err(EngineError::NotRefundable),
+        };
 
-        if let TransactionType::Deposit {
-            client: _,
-            tx: _,
-            amount,
-        } = transaction
-        {
-            self.held -= amount;
-            self.total -= amount;
-            self.locked = true;
+        let currency = self.transaction_currency(tx);
+        let available = self.balance(&currency).available;
+
+        let dispute = self
+            .disputes
+            .entry(tx)
+            .or_insert_with(|| TransactionDispute::new(original));
+
+        let amount = amount.unwrap_or(dispute.undisputed());
+        if sign.is_sign_negative() && available < amount {
+            return Err(EngineError::InsufficientFunds);
         }
+        let refunded = dispute.refund(Some(amount))?;
+
+        let balance = self.balance_mut(&currency);
+        balance.available += sign * refunded;
+        balance.total += sign * refunded;
 
         Ok(())
     }
+
+    /// Accrue interest in the given currency and post the part of the accrued interest that
+    /// reaches the given precision. Interest is posted even if the account is locked. Returns the
+    /// posted amount.
+    pub fn accrue_interest(
+        &mut self,
+        interest: Decimal,
+        currency: &Currency,
+        precision: u32,
+    ) -> Decimal {
+        let balance = self.balance_mut(currency);
+        balance.accrued_interest += interest;
+
+        let posted = postable(balance.accrued_interest, precision);
+        balance.accrued_interest -= posted;
+        balance.available += posted;
+        balance.total += posted;
+
+        if !posted.is_zero() {
+            info!(
+                "Interest - client {}, amount {} {}",
+                self.client, posted, currency
+            );
+        }
+
+        posted
+    }
+
+    /// Credit the funds returned by the chargeback of a transfer sent by current client. The
+    /// funds are returned even if the account is locked.
+    pub fn return_transfer(&mut self, amount: Decimal, tx: TxId, currency: &Currency) {
+        info!(
+            "Transfer returned - client {}, tx {}, amount {} {}",
+            self.client, tx, amount, currency
+        );
+
+        let balance = self.balance_mut(currency);
+        balance.available += amount;
+        balance.total += amount;
+    }
+
+    /// Number of transactions that have been disputed at least once
+    pub fn disputed_transactions(&self) -> usize {
+        self.disputes
+            .values()
+            .filter(|dispute| dispute.history().len() > 1)
+            .count()
+    }
+
+    /// Dispute of the given transaction id, if it has been disputed
+    pub fn transaction_dispute(&self, tx: TxId) -> Option<&TransactionDispute> {
+        self.disputes.get(&tx)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use rust_decimal::Decimal;
 
-    use crate::types::TransactionType;
+    use crate::{
+        clock::{Instant, Window},
+        currency::Currency,
+        dispute::{DisputePolicy, DisputeState},
+        error::EngineError,
+        ids::{ClientId, TxId},
+        types::TransactionType,
+    };
 
     use super::Client;
 
     #[test]
     fn test_deposit() {
-        let client_id = 1;
-        let tx = 1;
+        let currency = Currency::default();
+        let client_id = ClientId(1);
+        let tx = TxId::from(1);
         let amount = Decimal::new(2, 4);
         let mut client = Client::new(client_id);
         let transaction = TransactionType::Deposit {
             client: client_id,
             tx,
             amount,
+            currency: None,
         };
 
-        client.deposit(amount, tx).expect("Deposit failed.");
+        client
+            .deposit(amount, tx, &currency)
+            .expect("Deposit failed.");
 
-        assert_eq!(client.available, amount);
-        assert_eq!(client.total, amount);
-        assert_eq!(client.held, Decimal::new(0, 4));
+        assert_eq!(client.balance(&currency).available, amount);
+        assert_eq!(client.balance(&currency).total, amount);
+        assert_eq!(client.balance(&currency).held, Decimal::new(0, 4));
 
         client.processed_transactions.insert(tx, transaction);
 
         // try to process the same transaction again
-        assert!(client.deposit(amount, tx).is_err());
+        assert!(client.deposit(amount, tx, &currency).is_err());
 
-        let tx2 = 2;
+        let tx2 = TxId::from(2);
         let amount2 = Decimal::new(1, 4);
 
-        client.deposit(amount2, tx2).expect("Deposit failed.");
+        client
+            .deposit(amount2, tx2, &currency)
+            .expect("Deposit failed.");
 
-        assert_eq!(client.available, amount + amount2);
-        assert_eq!(client.total, amount + amount2);
-        assert_eq!(client.held, Decimal::new(0, 4));
+        assert_eq!(client.balance(&currency).available, amount + amount2);
+        assert_eq!(client.balance(&currency).total, amount + amount2);
+        assert_eq!(client.balance(&currency).held, Decimal::new(0, 4));
 
         // try to deposit a negative amount
-        assert!(client.deposit(Decimal::new(-1, 4), 3).is_err());
+        assert!(
+            client
+                .deposit(Decimal::new(-1, 4), TxId::from(3), &currency)
+                .is_err()
+        );
     }
 
     #[test]
     fn test_withdrawal() {
-        let client_id = 1;
+        let currency = Currency::default();
+        let client_id = ClientId(1);
         let mut client = Client::new(client_id);
 
         client
-            .deposit(Decimal::new(4, 4), 1)
+            .deposit(Decimal::new(4, 4), TxId::from(1), &currency)
             .expect("Deposit failed.");
 
         client
-            .withdrawal(Decimal::new(2, 4), 2)
+            .withdrawal(Decimal::new(2, 4), TxId::from(2), &currency)
             .expect("Withdrawal failed");
 
-        assert_eq!(client.available, Decimal::new(2, 4));
-        assert_eq!(client.total, Decimal::new(2, 4));
-        assert_eq!(client.held, Decimal::new(0, 4));
+        assert_eq!(client.balance(&currency).available, Decimal::new(2, 4));
+        assert_eq!(client.balance(&currency).total, Decimal::new(2, 4));
+        assert_eq!(client.balance(&currency).held, Decimal::new(0, 4));
 
         // negative amount
-        assert!(client.withdrawal(Decimal::new(-1, 4), 3).is_err());
+        assert!(
+            client
+                .withdrawal(Decimal::new(-1, 4), TxId::from(3), &currency)
+                .is_err()
+        );
 
         // insufficient funds
-        assert!(client.withdrawal(Decimal::new(5, 4), 4).is_err());
+        assert!(
+            client
+                .withdrawal(Decimal::new(5, 4), TxId::from(4), &currency)
+                .is_err()
+        );
     }
 
     #[test]
     fn test_dispute() {
-        let client_id = 1;
-        let tx = 1;
+        let currency = Currency::default();
+        let client_id = ClientId(1);
+        let tx = TxId::from(1);
         let amount = Decimal::new(2, 0);
         let mut client = Client::new(client_id);
 
         // try to dispute a transaction that does not exist
-        assert!(client.dispute(tx).is_err());
+        assert!(
+            client
+                .dispute(tx, None, &DisputePolicy::default(), Instant::default())
+                .is_err()
+        );
 
         let transaction = TransactionType::Deposit {
             client: client_id,
             tx,
             amount,
+            currency: None,
         };
-        client.deposit(amount, tx).expect("Deposit failed.");
+        client
+            .deposit(amount, tx, &currency)
+            .expect("Deposit failed.");
         client.processed_transactions.insert(tx, transaction);
 
-        client.dispute(tx).expect("Could not dispute transaction.");
+        client
+            .dispute(tx, None, &DisputePolicy::default(), Instant::default())
+            .expect("Could not dispute transaction.");
 
-        assert_eq!(client.available, Decimal::new(0, 0));
-        assert_eq!(client.held, amount);
-        assert!(client.disputed_transactions.contains(&tx));
+        assert_eq!(client.balance(&currency).available, Decimal::new(0, 0));
+        assert_eq!(client.balance(&currency).held, amount);
+        assert_eq!(client.dispute_state(tx), Some(DisputeState::Disputed));
     }
 
     #[test]
     fn test_resolve() {
-        let client_id = 1;
-        let tx = 1;
+        let currency = Currency::default();
+        let client_id = ClientId(1);
+        let tx = TxId::from(1);
         let amount = Decimal::new(2, 0);
         let mut client = Client::new(client_id);
 
@@ -281,28 +718,34 @@ mod tests {
             client: client_id,
             tx,
             amount,
+            currency: None,
         };
-        client.deposit(amount, tx).expect("Deposit failed.");
+        client
+            .deposit(amount, tx, &currency)
+            .expect("Deposit failed.");
         client.processed_transactions.insert(tx, transaction);
 
         // try to resolve a transaction that is not under dispute
-        assert!(client.resolve(tx).is_err());
+        assert!(client.resolve(tx, None).is_err());
 
-        client.dispute(tx).expect("Could not dispute transaction.");
+        client
+            .dispute(tx, None, &DisputePolicy::default(), Instant::default())
+            .expect("Could not dispute transaction.");
 
         client
-            .resolve(tx)
+            .resolve(tx, None)
             .expect("Could not resolve disputed transaction.");
 
-        assert_eq!(client.available, amount);
-        assert_eq!(client.held, Decimal::new(0, 0));
-        assert!(!client.disputed_transactions.contains(&tx));
+        assert_eq!(client.balance(&currency).available, amount);
+        assert_eq!(client.balance(&currency).held, Decimal::new(0, 0));
+        assert_eq!(client.dispute_state(tx), Some(DisputeState::Resolved));
     }
 
     #[test]
     fn test_chargeback() {
-        let client_id = 1;
-        let tx = 1;
+        let currency = Currency::default();
+        let client_id = ClientId(1);
+        let tx = TxId::from(1);
         let amount = Decimal::new(1, 0);
         let mut client = Client::new(client_id);
 
@@ -310,32 +753,288 @@ mod tests {
             client: client_id,
             tx,
             amount,
+            currency: None,
         };
-        client.deposit(amount, tx).expect("Deposit failed.");
+        client
+            .deposit(amount, tx, &currency)
+            .expect("Deposit failed.");
         client.processed_transactions.insert(tx, transaction);
 
         // try to chargeback a transaction that is not under dispute
-        assert!(client.chargeback(tx).is_err());
+        assert!(client.chargeback(tx, None).is_err());
 
-        client.dispute(tx).expect("Could not dispute transaction.");
+        client
+            .dispute(tx, None, &DisputePolicy::default(), Instant::default())
+            .expect("Could not dispute transaction.");
 
-        assert_eq!(client.available, Decimal::new(0, 0));
-        assert_eq!(client.held, Decimal::new(1, 0));
-        assert_eq!(client.total, Decimal::new(1, 0));
+        assert_eq!(client.balance(&currency).available, Decimal::new(0, 0));
+        assert_eq!(client.balance(&currency).held, Decimal::new(1, 0));
+        assert_eq!(client.balance(&currency).total, Decimal::new(1, 0));
 
         // check that withdrawal fails with funds under dispute
-        assert!(client.withdrawal(Decimal::new(1, 0), 2).is_err());
+        assert!(
+            client
+                .withdrawal(Decimal::new(1, 0), TxId::from(2), &currency)
+                .is_err()
+        );
 
         client
-            .chargeback(tx)
+            .chargeback(tx, None)
             .expect("Could not chargeback transaction.");
-        assert_eq!(client.available, Decimal::new(0, 0));
-        assert_eq!(client.held, Decimal::new(0, 0));
-        assert_eq!(client.total, Decimal::new(0, 0));
+        assert_eq!(client.dispute_state(tx), Some(DisputeState::ChargedBack));
+        assert_eq!(client.balance(&currency).available, Decimal::new(0, 0));
+        assert_eq!(client.balance(&currency).held, Decimal::new(0, 0));
+        assert_eq!(client.balance(&currency).total, Decimal::new(0, 0));
 
-        assert!(client.chargeback(tx).is_err());
+        assert!(client.chargeback(tx, None).is_err());
 
         // check that the account is now locked
-        assert!(client.deposit(Decimal::new(1, 0), 10).is_err());
+        assert!(
+            client
+                .deposit(Decimal::new(1, 0), TxId::from(10), &currency)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_redispute() {
+        let currency = Currency::default();
+        let client_id = ClientId(1);
+        let tx = TxId::from(1);
+        let amount = Decimal::new(2, 0);
+        let mut client = Client::new(client_id);
+        let policy = DisputePolicy {
+            max_disputes: Some(2),
+            ..Default::default()
+        };
+
+        assert_eq!(client.dispute_history(tx), None);
+
+        let transaction = TransactionType::Deposit {
+            client: client_id,
+            tx,
+            amount,
+            currency: None,
+        };
+        client
+            .deposit(amount, tx, &currency)
+            .expect("Deposit failed.");
+        client.processed_transactions.insert(tx, transaction);
+
+        assert_eq!(client.dispute_state(tx), Some(DisputeState::Settled));
+
+        for _ in 0..2 {
+            client
+                .dispute(tx, None, &policy, Instant::default())
+                .expect("Could not dispute transaction.");
+            client
+                .resolve(tx, None)
+                .expect("Could not resolve disputed transaction.");
+        }
+
+        // the transaction reached the maximum number of disputes
+        assert!(
+            client
+                .dispute(tx, None, &policy, Instant::default())
+                .is_err()
+        );
+        assert_eq!(client.balance(&currency).available, amount);
+        assert_eq!(client.balance(&currency).held, Decimal::new(0, 0));
+
+        assert_eq!(
+            client.dispute_history(tx),
+            Some(
+                [
+                    DisputeState::Settled,
+                    DisputeState::Disputed,
+                    DisputeState::Resolved,
+                    DisputeState::Disputed,
+                    DisputeState::Resolved,
+                ]
+                .as_slice()
+            )
+        );
+    }
+
+    #[test]
+    fn test_partial_dispute() {
+        let currency = Currency::default();
+        let client_id = ClientId(1);
+        let tx = TxId::from(1);
+        let amount = Decimal::new(10, 0);
+        let mut client = Client::new(client_id);
+        let policy = DisputePolicy::default();
+
+        let transaction = TransactionType::Deposit {
+            client: client_id,
+            tx,
+            amount,
+            currency: None,
+        };
+        client
+            .deposit(amount, tx, &currency)
+            .expect("Deposit failed.");
+        client.processed_transactions.insert(tx, transaction);
+
+        client
+            .dispute(tx, Some(Decimal::new(4, 0)), &policy, Instant::default())
+            .expect("Could not dispute transaction.");
+        client
+            .dispute(tx, Some(Decimal::new(5, 0)), &policy, Instant::default())
+            .expect("Could not dispute transaction.");
+
+        assert_eq!(client.balance(&currency).available, Decimal::new(1, 0));
+        assert_eq!(client.balance(&currency).held, Decimal::new(9, 0));
+
+        // several partial disputes can't exceed the deposited amount
+        assert!(
+            client
+                .dispute(tx, Some(Decimal::new(2, 0)), &policy, Instant::default())
+                .is_err()
+        );
+        // a zero amount is not the whole amount
+        assert_eq!(
+            client.resolve(tx, Some(Decimal::ZERO)),
+            Err(EngineError::NonPositiveAmount)
+        );
+
+        client
+            .resolve(tx, Some(Decimal::new(3, 0)))
+            .expect("Could not resolve disputed transaction.");
+
+        assert_eq!(client.balance(&currency).available, Decimal::new(4, 0));
+        assert_eq!(client.balance(&currency).held, Decimal::new(6, 0));
+        assert_eq!(client.dispute_state(tx), Some(DisputeState::Disputed));
+
+        client
+            .chargeback(tx, Some(Decimal::new(2, 0)))
+            .expect("Could not chargeback transaction.");
+
+        assert_eq!(client.balance(&currency).available, Decimal::new(8, 0));
+        assert_eq!(client.balance(&currency).held, Decimal::new(0, 0));
+        assert_eq!(client.balance(&currency).total, Decimal::new(8, 0));
+        assert!(client.locked);
+    }
+
+    #[test]
+    fn test_dispute_window() {
+        let currency = Currency::default();
+        let client_id = ClientId(1);
+        let amount = Decimal::new(2, 0);
+        let mut client = Client::new(client_id);
+        let policy = DisputePolicy {
+            window: Some(Window::Days(120)),
+            ..Default::default()
+        };
+        let deposited_at = Instant {
+            seq: 1,
+            timestamp: Some(1_700_000_000),
+        };
+
+        for tx in [1, 2].map(TxId::from) {
+            client
+                .deposit(amount, tx, &currency)
+                .expect("Deposit failed.");
+            client.record_transaction(
+                tx,
+                TransactionType::Deposit {
+                    client: client_id,
+                    tx,
+                    amount,
+                    currency: None,
+                },
+                deposited_at,
+            );
+        }
+
+        let within_window = Instant {
+            seq: 3,
+            timestamp: Some(1_700_000_000 + 120 * 86_400),
+        };
+        client
+            .dispute(TxId::from(1), None, &policy, within_window)
+            .expect("Could not dispute transaction.");
+
+        let past_window = Instant {
+            seq: 4,
+            timestamp: Some(1_700_000_000 + 120 * 86_400 + 1),
+        };
+        assert!(
+            client
+                .dispute(TxId::from(2), None, &policy, past_window)
+                .is_err()
+        );
+        assert_eq!(client.balance(&currency).held, amount);
+    }
+
+    #[test]
+    fn test_refund() {
+        let currency = Currency::default();
+        let client_id = ClientId(1);
+        let mut client = Client::new(client_id);
+        let policy = DisputePolicy::default();
+
+        let deposit = TransactionType::Deposit {
+            client: client_id,
+            tx: TxId::from(1),
+            amount: Decimal::new(10, 0),
+            currency: None,
+        };
+        client
+            .deposit(Decimal::new(10, 0), TxId::from(1), &currency)
+            .expect("Deposit failed.");
+        client.processed_transactions.insert(TxId::from(1), deposit);
+
+        let withdrawal = TransactionType::Withdrawal {
+            client: client_id,
+            tx: TxId::from(2),
+            amount: Decimal::new(3, 0),
+            currency: None,
+        };
+        client
+            .withdrawal(Decimal::new(3, 0), TxId::from(2), &currency)
+            .expect("Withdrawal failed.");
+        client
+            .processed_transactions
+            .insert(TxId::from(2), withdrawal);
+
+        client
+            .dispute(
+                TxId::from(1),
+                Some(Decimal::new(4, 0)),
+                &policy,
+                Instant::default(),
+            )
+            .expect("Could not dispute transaction.");
+
+        // the rest of the deposit is refundable, but not covered by the available funds
+        assert!(client.refund(TxId::from(1), None).is_err());
+        client
+            .refund(TxId::from(1), Some(Decimal::new(2, 0)))
+            .expect("Could not refund deposit.");
+        client
+            .refund(TxId::from(2), None)
+            .expect("Could not refund withdrawal.");
+        assert!(
+            client
+                .refund(TxId::from(2), Some(Decimal::new(1, 0)))
+                .is_err()
+        );
+
+        assert_eq!(client.balance(&currency).available, Decimal::new(4, 0));
+        assert_eq!(client.balance(&currency).held, Decimal::new(4, 0));
+        assert_eq!(client.balance(&currency).total, Decimal::new(8, 0));
+
+        // the refunded amount can't be disputed anymore
+        client
+            .resolve(TxId::from(1), None)
+            .expect("Could not resolve disputed transaction.");
+        assert_eq!(
+            client
+                .transaction_dispute(TxId::from(1))
+                .map(|dispute| dispute.undisputed()),
+            Some(Decimal::new(8, 0))
+        );
+        assert!(!client.locked);
     }
 }
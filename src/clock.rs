@@ -0,0 +1,73 @@
+use serde::{Deserialize, Serialize};
+
+/// Number of seconds in a day
+const SECONDS_PER_DAY: u64 = 86_400;
+
+/// Moment at which a transaction has been processed: its position in the input and, when the
+/// input provides one, its timestamp.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Instant {
+    /// Ingestion order sequence number
+    pub seq: u64,
+    /// Unix timestamp in seconds
+    pub timestamp: Option<u64>,
+}
+
+impl Instant {
+    /// Number of days since the unix epoch, if the timestamp is known
+    pub fn day(&self) -> Option<u64> {
+        self.timestamp.map(|timestamp| timestamp / SECONDS_PER_DAY)
+    }
+}
+
+/// Length of a time period, either in days or in number of processed transactions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Window {
+    Days(u64),
+    /// Transactions processed by the engine, or by the client for the velocity controls of its
+    /// activity
+    Transactions(u64),
+}
+
+impl Window {
+    /// Check if the window has passed between the two instants. Windows in days can only be
+    /// checked when both instants have a timestamp.
+    pub fn has_elapsed(&self, since: Instant, now: Instant) -> bool {
+        match self {
+            Self::Days(days) => match (since.timestamp, now.timestamp) {
+                (Some(since), Some(now)) => now.saturating_sub(since) > days * SECONDS_PER_DAY,
+                _ => false,
+            },
+            Self::Transactions(transactions) => now.seq.saturating_sub(since.seq) > *transactions,
+        }
+    }
+}
+
+/// Clock of the engine, advanced by each processed transaction.
+#[derive(Debug, Default)]
+pub struct Clock {
+    now: Instant,
+}
+
+impl Clock {
+    /// Clock resuming from the given instant
+    pub fn starting_at(now: Instant) -> Self {
+        Self { now }
+    }
+
+    pub fn now(&self) -> Instant {
+        self.now
+    }
+
+    /// Move to the next transaction. Transactions without a timestamp, or with a timestamp in
+    /// the past, keep the latest known timestamp.
+    pub fn tick(&mut self, timestamp: Option<u64>) -> Instant {
+        self.now = Instant {
+            seq: self.now.seq + 1,
+            timestamp: self.now.timestamp.max(timestamp),
+        };
+
+        self.now
+    }
+}
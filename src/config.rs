@@ -0,0 +1,105 @@
+use std::{collections::HashMap, fs::File};
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    authorization::AuthorizationPolicy,
+    conversion::ConversionConfig,
+    currency::{Currency, CurrencyConfig},
+    dispute::DisputePolicy,
+    fees::FeeSchedule,
+    interest::InterestConfig,
+    limits::LimitsConfig,
+    output::{OutputFormat, OutputOrder},
+    reconcile::ReconciliationConfig,
+    registry::RegistryConfig,
+    risk::RuleConfig,
+    types::DECIMAL_PRECISION,
+};
+
+/// Configuration of the transactions engine, read from a JSON file.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct EngineConfig {
+    /// Rules applied to disputes
+    pub dispute: DisputePolicy,
+    /// Rules applied to authorizations
+    pub authorization: AuthorizationPolicy,
+    /// Fees charged for each transaction type
+    pub fees: FeeSchedule,
+    /// Interest paid on the available funds, `None` when no interest is paid
+    pub interest: Option<InterestConfig>,
+    /// Risk limits of the clients
+    pub limits: LimitsConfig,
+    /// Risk rules run before each transaction is applied, in order
+    pub risk_rules: Vec<RuleConfig>,
+    /// Currency of the transactions without one
+    pub default_currency: Currency,
+    /// Settings of each currency
+    pub currencies: HashMap<Currency, CurrencyConfig>,
+    /// Conversions between currencies
+    pub conversion: ConversionConfig,
+    /// Accounts known before any transaction is processed
+    pub registry: RegistryConfig,
+    /// Currency the holdings of the clients are valued in
+    pub reporting_currency: Option<Currency>,
+    /// Reconciliation against an external balance file
+    pub reconciliation: ReconciliationConfig,
+    /// Format the state of the clients is written in
+    pub output_format: OutputFormat,
+    /// Order the clients are written in
+    pub output_order: OutputOrder,
+    /// File the events generated by the engine are written to
+    pub events_output: Option<String>,
+    /// File the ledger lines are written to
+    pub ledger_output: Option<String>,
+    /// File the alerts raised by the risk rules are written to
+    pub alerts_output: Option<String>,
+    /// File the valuation of the holdings of the clients in the reporting currency is written to
+    pub valuation_output: Option<String>,
+    /// File the state of the engine is saved to after processing the input, as a snapshot
+    pub snapshot_output: Option<String>,
+    /// File the metrics are written to after processing the input, in the Prometheus text format
+    pub metrics_output: Option<String>,
+}
+
+impl EngineConfig {
+    /// Load the configuration from the given JSON file
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let file =
+            File::open(path).map_err(|err| format!("Could not open config file: {err:?}"))?;
+
+        serde_json::from_reader(file).map_err(|err| format!("Invalid config file: {err:?}"))
+    }
+
+    /// Build the configuration from layers of JSON settings, each layer overriding the settings of
+    /// the previous ones
+    pub fn from_layers(layers: impl IntoIterator<Item = Value>) -> Result<Self, String> {
+        let mut settings = Value::Object(Default::default());
+        for layer in layers {
+            merge(&mut settings, layer);
+        }
+
+        serde_json::from_value(settings).map_err(|err| format!("Invalid configuration: {err}"))
+    }
+
+    /// Decimal precision of the amounts in the given currency
+    pub fn precision(&self, currency: &Currency) -> u32 {
+        self.currencies
+            .get(currency)
+            .map_or(DECIMAL_PRECISION, |config| config.precision)
+    }
+}
+
+/// Merge the layer into the settings, objects are merged key by key and other values are replaced
+fn merge(settings: &mut Value, layer: Value) {
+    match (settings, layer) {
+        (Value::Object(settings), Value::Object(layer)) => {
+            for (key, value) in layer {
+                merge(settings.entry(key).or_insert(Value::Null), value);
+            }
+        }
+        (settings, layer) => *settings = layer,
+    }
+}
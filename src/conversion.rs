@@ -0,0 +1,173 @@
+use std::{collections::HashMap, fs::File, io::Read};
+
+use csv::{ReaderBuilder, Trim};
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{Deserialize, Serialize};
+
+use crate::{currency::Currency, types::parse_amount};
+
+/// Settings of the conversions between currencies.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConversionConfig {
+    /// CSV file with the conversion rates, loaded at startup
+    pub rates: Option<String>,
+    /// Percentage of the converted amount kept by the house
+    pub spread_percent: Decimal,
+}
+
+/// Conversion rate read from the rates file.
+#[derive(Debug, Deserialize)]
+struct RateRecord {
+    /// Currency converted from
+    from: String,
+    /// Currency converted to
+    to: String,
+    /// Amount of `to` for one unit of `from`
+    rate: String,
+    /// Unix timestamp in seconds from which the rate applies
+    #[serde(default)]
+    timestamp: Option<u64>,
+}
+
+/// Currency converted from and currency converted to.
+type CurrencyPair = (Currency, Currency);
+
+/// Conversion rates between pairs of currencies, optionally changing over time.
+#[derive(Debug, Default)]
+pub struct RateTable {
+    /// Rates of each pair, ordered by the timestamp from which they apply
+    rates: HashMap<CurrencyPair, Vec<(Option<u64>, Decimal)>>,
+}
+
+impl RateTable {
+    /// Load the rates from the given CSV file
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let file = File::open(path).map_err(|err| format!("Could not open rates file: {err:?}"))?;
+
+        Self::from_reader(file)
+    }
+
+    /// Load the rates from CSV with the `from`, `to`, `rate` and optional `timestamp` columns
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self, String> {
+        let mut reader = ReaderBuilder::new()
+            .flexible(true)
+            .has_headers(true)
+            .trim(Trim::All)
+            .from_reader(reader);
+
+        let mut table = Self::default();
+        for line in reader.deserialize() {
+            let record: RateRecord = line.map_err(|err| format!("Invalid rate: {err:?}"))?;
+
+            table.insert(
+                Currency::from(record.from),
+                Currency::from(record.to),
+                parse_amount(record.rate)?,
+                record.timestamp,
+            );
+        }
+
+        Ok(table)
+    }
+
+    /// Add the rate converting `from` into `to`, applying from the given timestamp
+    pub fn insert(&mut self, from: Currency, to: Currency, rate: Decimal, timestamp: Option<u64>) {
+        let rates = self.rates.entry((from, to)).or_default();
+
+        rates.push((timestamp, rate));
+        rates.sort_by_key(|(timestamp, _)| *timestamp);
+    }
+
+    /// Rate converting `from` into `to` at the given timestamp. Without a timestamp the latest
+    /// rate is used.
+    pub fn rate(&self, from: &Currency, to: &Currency, at: Option<u64>) -> Option<Decimal> {
+        if from == to {
+            return Some(Decimal::ONE);
+        }
+
+        self.rates
+            .get(&(from.clone(), to.clone()))?
+            .iter()
+            .rev()
+            .find(|(timestamp, _)| at.is_none() || *timestamp <= at)
+            .map(|(_, rate)| *rate)
+    }
+}
+
+/// Result of a conversion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Conversion {
+    /// Amount credited to the client
+    pub credited: Decimal,
+    /// Amount kept by the house, the spread and the rounding remainder
+    pub revenue: Decimal,
+}
+
+/// Convert an amount at the given rate, keeping the spread for the house. The converted amount is
+/// rounded half away from zero to the target precision, the client is credited that amount
+/// minus the spread rounded towards zero, and the house keeps the difference.
+pub fn convert(
+    amount: Decimal,
+    rate: Decimal,
+    spread_percent: Decimal,
+    precision: u32,
+) -> Conversion {
+    let converted =
+        (amount * rate).round_dp_with_strategy(precision, RoundingStrategy::MidpointAwayFromZero);
+    let credited = (converted * (Decimal::ONE_HUNDRED - spread_percent) / Decimal::ONE_HUNDRED)
+        .round_dp_with_strategy(precision, RoundingStrategy::ToZero);
+
+    Conversion {
+        credited,
+        revenue: converted - credited,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+
+    use crate::currency::Currency;
+
+    use super::{Conversion, RateTable, convert};
+
+    #[test]
+    fn test_rates() {
+        let rates = "from, to, rate, timestamp\n\
+            EUR, USD, 1.10,\n\
+            eur, usd, 1.20, 1700000000\n";
+        let table = RateTable::from_reader(rates.as_bytes()).expect("Could not load rates.");
+        let eur = Currency::from("EUR");
+        let usd = Currency::from("USD");
+
+        assert_eq!(
+            table.rate(&eur, &usd, Some(1_600_000_000)),
+            Some(Decimal::new(110, 2))
+        );
+        assert_eq!(
+            table.rate(&eur, &usd, Some(1_700_000_000)),
+            Some(Decimal::new(120, 2))
+        );
+        assert_eq!(table.rate(&eur, &usd, None), Some(Decimal::new(120, 2)));
+        assert_eq!(table.rate(&usd, &eur, None), None);
+        assert_eq!(table.rate(&usd, &usd, None), Some(Decimal::ONE));
+    }
+
+    #[test]
+    fn test_convert() {
+        // 10.01 * 1.2345 = 12.357345, rounded to 12.36, minus a 1% spread of 0.1236
+        assert_eq!(
+            convert(
+                Decimal::new(1001, 2),
+                Decimal::new(12345, 4),
+                Decimal::ONE,
+                2
+            ),
+            Conversion {
+                credited: Decimal::new(1223, 2),
+                revenue: Decimal::new(13, 2),
+            }
+        );
+    }
+}
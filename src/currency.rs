@@ -0,0 +1,75 @@
+use std::fmt;
+
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+/// Currency code, such as `EUR` or `USD`. Codes are case insensitive and kept in upper case.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(from = "String")]
+pub struct Currency(String);
+
+impl From<String> for Currency {
+    fn from(code: String) -> Self {
+        Self(code.to_uppercase())
+    }
+}
+
+impl From<&str> for Currency {
+    fn from(code: &str) -> Self {
+        Self(code.to_uppercase())
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Settings of a currency.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct CurrencyConfig {
+    /// Number of decimals of the minor unit
+    pub precision: u32,
+}
+
+/// Funds of a client in a currency.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Balance {
+    /// Available amount
+    pub available: Decimal,
+    /// Amount under dispute
+    pub held: Decimal,
+    /// Amount reserved by pending authorizations
+    pub reserved: Decimal,
+    /// Total funds
+    pub total: Decimal,
+    /// Fees charged to the client
+    pub fees: Decimal,
+    /// Interest accrued and not posted yet, at the internal interest precision
+    pub accrued_interest: Decimal,
+}
+
+impl Balance {
+    /// Amount drawn on the credit line: how far the available funds are below zero, up to the
+    /// credit limit
+    pub fn credit_used(&self, credit_limit: Decimal) -> Decimal {
+        self.overdrawn().min(credit_limit.max(Decimal::ZERO))
+    }
+
+    /// How far the available funds are below zero beyond the credit limit, when a dispute holds
+    /// funds that have already been spent
+    pub fn shortfall(&self, credit_limit: Decimal) -> Decimal {
+        (self.overdrawn() - credit_limit.max(Decimal::ZERO)).max(Decimal::ZERO)
+    }
+
+    /// How far the available funds are below zero. Negating zero would keep its sign and show
+    /// as `-0`.
+    fn overdrawn(&self) -> Decimal {
+        if self.available.is_sign_negative() {
+            -self.available
+        } else {
+            Decimal::ZERO
+        }
+    }
+}
@@ -0,0 +1,423 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::{Read, Write},
+    str::FromStr,
+};
+
+use csv::{ReaderBuilder, Trim, Writer};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use crate::{
+    client::Client, currency::Currency, engine::TransactionsEngine, ids::ClientId, ledger::Account,
+    snapshot::Snapshot,
+};
+
+/// Funds of a client in a currency, and whether the client is locked.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct AccountState {
+    pub available: Decimal,
+    pub held: Decimal,
+    pub total: Decimal,
+    pub locked: bool,
+}
+
+/// State of the accounts of all the clients, by client and currency.
+pub type State = BTreeMap<(ClientId, Currency), AccountState>;
+
+/// Change of the account of a client in a currency. The account is missing before when it has been
+/// opened, and after when it has been closed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub client: ClientId,
+    pub currency: Currency,
+    pub before: Option<AccountState>,
+    pub after: Option<AccountState>,
+}
+
+/// Row of the output of the engine, only with the columns compared. Amounts are kept as text to be
+/// parsed as decimals.
+#[derive(Debug, Deserialize)]
+struct StateRecord {
+    client: ClientId,
+    #[serde(default)]
+    currency: Currency,
+    available: String,
+    held: String,
+    total: String,
+    locked: bool,
+}
+
+/// State of the accounts of the clients of the engine
+pub fn state(engine: &TransactionsEngine) -> State {
+    engine
+        .clients()
+        .into_iter()
+        .flat_map(|client| {
+            engine
+                .balances(client)
+                .into_iter()
+                .map(move |(currency, balance)| {
+                    (
+                        (client.client, currency.clone()),
+                        AccountState {
+                            available: balance.available,
+                            held: balance.held,
+                            total: balance.total,
+                            locked: client.locked,
+                        },
+                    )
+                })
+        })
+        .collect()
+}
+
+/// State of the accounts of the clients of the snapshot
+pub fn snapshot_state(snapshot: &Snapshot) -> State {
+    state(&TransactionsEngine::new().with_snapshot(snapshot.clone()))
+}
+
+/// State of the accounts read from the CSV output of the engine
+pub fn read_state<R: Read>(reader: R) -> Result<State, String> {
+    ReaderBuilder::new()
+        .trim(Trim::All)
+        .flexible(true)
+        .from_reader(reader)
+        .deserialize()
+        .map(|record| {
+            let record: StateRecord = record.map_err(|err| format!("Invalid state: {err}"))?;
+            let amount = |amount: &str| {
+                Decimal::from_str(amount)
+                    .map_err(|err| format!("Invalid amount {amount:?} in state: {err}"))
+            };
+
+            Ok((
+                (record.client, record.currency),
+                AccountState {
+                    available: amount(&record.available)?,
+                    held: amount(&record.held)?,
+                    total: amount(&record.total)?,
+                    locked: record.locked,
+                },
+            ))
+        })
+        .collect()
+}
+
+/// Differences between two states of the accounts.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StateDiff {
+    /// Accounts that changed, by client and currency
+    pub changes: Vec<Change>,
+    /// Clients only in the second state
+    pub added: Vec<ClientId>,
+    /// Clients only in the first state
+    pub removed: Vec<ClientId>,
+    /// Transactions and ledger lines explaining the changes of each client, when the history of
+    /// both states is known
+    pub transactions: Option<BTreeMap<ClientId, Vec<String>>>,
+}
+
+impl StateDiff {
+    pub fn between(before: &State, after: &State) -> Self {
+        let keys: BTreeSet<_> = before.keys().chain(after.keys()).collect();
+        let changes = keys
+            .into_iter()
+            .filter_map(|key| {
+                let (before, after) = (before.get(key), after.get(key));
+                (before != after).then(|| Change {
+                    client: key.0,
+                    currency: key.1.clone(),
+                    before: before.cloned(),
+                    after: after.cloned(),
+                })
+            })
+            .collect();
+
+        let clients = |state: &State| -> BTreeSet<ClientId> {
+            state.keys().map(|(client, _)| *client).collect()
+        };
+        let (before, after) = (clients(before), clients(after));
+
+        Self {
+            changes,
+            added: after.difference(&before).copied().collect(),
+            removed: before.difference(&after).copied().collect(),
+            transactions: None,
+        }
+    }
+
+    /// Differences between two snapshots, with the transactions explaining them
+    pub fn between_snapshots(before: &Snapshot, after: &Snapshot) -> Self {
+        let mut diff = Self::between(&snapshot_state(before), &snapshot_state(after));
+
+        let clients: BTreeSet<_> = diff.changes.iter().map(|change| change.client).collect();
+        diff.transactions = Some(
+            clients
+                .into_iter()
+                .map(|client| (client, explain(before, after, client)))
+                .collect(),
+        );
+
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// `added` or `removed` for the accounts of added or removed clients, `opened` or `closed` for
+    /// the accounts of the other clients in a new or former currency, otherwise `changed`
+    pub fn status(&self, change: &Change) -> &'static str {
+        match (&change.before, &change.after) {
+            _ if self.added.contains(&change.client) => "added",
+            _ if self.removed.contains(&change.client) => "removed",
+            (None, _) => "opened",
+            (_, None) => "closed",
+            _ => "changed",
+        }
+    }
+
+    /// Write the changes as CSV, with the values before and after each change, and the
+    /// transactions explaining them when they are known
+    pub fn write<W: Write>(&self, writer: W) -> Result<(), String> {
+        let mut writer = Writer::from_writer(writer);
+
+        let mut header = vec![
+            "status",
+            "client",
+            "currency",
+            "available_before",
+            "available_after",
+            "held_before",
+            "held_after",
+            "total_before",
+            "total_after",
+            "locked_before",
+            "locked_after",
+        ];
+        if self.transactions.is_some() {
+            header.push("transactions");
+        }
+        writer
+            .write_record(header)
+            .map_err(|err| format!("Could not write record: {err:?}"))?;
+
+        for change in &self.changes {
+            let before = fields(change.before.as_ref());
+            let after = fields(change.after.as_ref());
+
+            let mut record = vec![
+                self.status(change).to_string(),
+                change.client.to_string(),
+                change.currency.to_string(),
+            ];
+            record.extend(
+                before
+                    .into_iter()
+                    .zip(after)
+                    .flat_map(|(before, after)| [before, after]),
+            );
+            if let Some(transactions) = &self.transactions {
+                record.push(
+                    transactions
+                        .get(&change.client)
+                        .map(|transactions| transactions.join("; "))
+                        .unwrap_or_default(),
+                );
+            }
+
+            writer
+                .write_record(record)
+                .map_err(|err| format!("Could not write record: {err:?}"))?;
+        }
+
+        writer
+            .flush()
+            .map_err(|err| format!("Could not flush records: {err:?}"))
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "changes": self.changes.iter().map(|change| {
+                let mut change_json = json!({
+                    "status": self.status(change),
+                    "client": change.client,
+                    "currency": change.currency.to_string(),
+                    "before": change.before,
+                    "after": change.after,
+                });
+                if let Some(transactions) = &self.transactions {
+                    change_json["transactions"] = json!(transactions.get(&change.client));
+                }
+                change_json
+            }).collect::<Vec<_>>(),
+            "added": self.added,
+            "removed": self.removed,
+        })
+    }
+}
+
+/// Fields of the account in the order of the output, empty when there is no account
+fn fields(account: Option<&AccountState>) -> [String; 4] {
+    account.map_or_else(Default::default, |account| {
+        [
+            account.available.to_string(),
+            account.held.to_string(),
+            account.total.to_string(),
+            account.locked.to_string(),
+        ]
+    })
+}
+
+/// Transactions processed, disputes moved and ledger lines posted for the client between the two
+/// snapshots
+fn explain(before: &Snapshot, after: &Snapshot, client: ClientId) -> Vec<String> {
+    let find = |snapshot: &Snapshot| -> Option<Client> {
+        snapshot
+            .clients
+            .iter()
+            .find(|candidate| candidate.client == client)
+            .cloned()
+    };
+    let (before_client, Some(after_client)) = (find(before), find(after)) else {
+        return Vec::new();
+    };
+
+    let mut transactions: Vec<_> = after_client
+        .processed_transactions
+        .iter()
+        .map(|(tx, transaction)| {
+            (
+                after_client.processed_at(*tx).unwrap_or_default(),
+                tx,
+                transaction,
+            )
+        })
+        .collect();
+    transactions.sort_by_key(|(at, tx, _)| (at.seq, **tx));
+
+    // New transactions first, then the disputes they moved
+    let mut explanations = Vec::new();
+    let mut disputes = Vec::new();
+    for (_, tx, transaction) in transactions {
+        let known = before_client
+            .as_ref()
+            .filter(|client| client.processed_transactions.contains_key(tx));
+        if known.is_none() {
+            let mut explanation = format!("{} {tx}", transaction.name());
+            if let Some((amount, _)) = transaction.funds() {
+                explanation += &format!(" of {amount}");
+            }
+            explanations.push(explanation);
+        }
+
+        // Dispute states reached since the first snapshot
+        let history = after_client.dispute_history(*tx).unwrap_or_default();
+        let known_states = known
+            .and_then(|client| client.dispute_history(*tx))
+            .map_or(1, <[_]>::len);
+        if history.len() > known_states {
+            let states: Vec<_> = history[known_states - 1..]
+                .iter()
+                .map(|state| format!("{state:?}"))
+                .collect();
+            disputes.push(format!("dispute of {tx}: {}", states.join(" -> ")));
+        }
+    }
+    explanations.append(&mut disputes);
+
+    for entry in after.ledger.entries() {
+        if entry.account == Account::Client(client) && entry.at.seq > before.now.seq {
+            let mut explanation = format!("{} of {}", entry.kind, entry.amount);
+            if let Some(tx) = entry.tx {
+                explanation += &format!(" for {tx}");
+            }
+            explanations.push(explanation);
+        }
+    }
+
+    explanations
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+
+    use crate::{
+        engine::TransactionsEngine,
+        ids::{ClientId, TxId},
+        types::TransactionType,
+    };
+
+    use super::{StateDiff, read_state};
+
+    #[test]
+    fn test_diff() {
+        let mut engine = TransactionsEngine::new();
+        let deposit = |client, tx, amount| TransactionType::Deposit {
+            client: ClientId(client),
+            tx: TxId::from(tx),
+            amount: Decimal::new(amount, 0),
+            currency: None,
+        };
+        for transaction in [deposit(1, 1, 10), deposit(2, 2, 5)] {
+            engine
+                .process_transaction(transaction)
+                .expect("Could not process deposit.");
+        }
+        let before = engine.snapshot();
+
+        for transaction in [
+            deposit(1, 3, 1),
+            TransactionType::Dispute {
+                client: ClientId(1),
+                tx: TxId::from(1),
+                amount: None,
+            },
+            deposit(3, 4, 7),
+        ] {
+            engine
+                .process_transaction(transaction)
+                .expect("Could not process transaction.");
+        }
+        let after = engine.snapshot();
+
+        let diff = StateDiff::between_snapshots(&before, &after);
+        assert_eq!(diff.changes.len(), 2);
+        assert_eq!(diff.added, [ClientId(3)]);
+        assert!(diff.removed.is_empty());
+
+        let mut output = Vec::new();
+        diff.write(&mut output).expect("Could not write diff.");
+        assert_eq!(
+            String::from_utf8(output).expect("Invalid output."),
+            "status,client,currency,available_before,available_after,held_before,held_after,\
+             total_before,total_after,locked_before,locked_after,transactions\n\
+             changed,1,,10,1,0,10,10,11,false,false,deposit 3 of 1; dispute of 1: Settled -> Disputed\n\
+             added,3,,,7,,0,,7,,false,deposit 4 of 7\n"
+        );
+
+        // The output of the engine can be compared as well, without the transactions
+        let before = read_state(
+            "client,currency,available,held,reserved,total,locked\n\
+             1,,10,0,0,10,false\n\
+             2,,5,0,0,5,false\n"
+                .as_bytes(),
+        )
+        .expect("Invalid state.");
+        let after =
+            read_state("client,available,held,total,locked\n1,10.0,0,10.0,true\n".as_bytes())
+                .expect("Invalid state.");
+        let diff = StateDiff::between(&before, &after);
+        assert_eq!(diff.status(&diff.changes[0]), "changed");
+        assert_eq!(
+            diff.changes[0].after.as_ref().map(|account| account.total),
+            Some(Decimal::new(100, 1))
+        );
+        assert_eq!(diff.status(&diff.changes[1]), "removed");
+        assert_eq!(diff.removed, [ClientId(2)]);
+        assert_eq!(diff.transactions, None);
+    }
+}
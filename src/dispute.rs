@@ -0,0 +1,358 @@
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    clock::{Instant, Window},
+    error::EngineError,
+};
+
+/// Dispute state of a processed transaction.
+///
+/// A transaction starts as `Settled`, can be `Disputed` and then either `Resolved` or
+/// `ChargedBack`. A resolved transaction can be disputed again if the policy allows it, while a
+/// chargeback is terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DisputeState {
+    Settled,
+    Disputed,
+    Resolved,
+    ChargedBack,
+}
+
+/// Rules applied when disputing a transaction.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DisputePolicy {
+    /// Maximum number of times the same transaction can be disputed, `None` for no limit
+    pub max_disputes: Option<u32>,
+    /// Period after the transaction in which it can be disputed, `None` for no limit
+    pub window: Option<Window>,
+    /// Automatic closing of the disputes left open for too long
+    pub expiry: Option<DisputeExpiry>,
+}
+
+impl Default for DisputePolicy {
+    fn default() -> Self {
+        Self {
+            max_disputes: Some(1),
+            window: None,
+            expiry: None,
+        }
+    }
+}
+
+/// Disputes open for longer than `after` are closed with the given action.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct DisputeExpiry {
+    pub after: Window,
+    pub action: ExpiryAction,
+}
+
+/// How an expired dispute is closed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExpiryAction {
+    Resolve,
+    Chargeback,
+}
+
+/// Dispute lifecycle of a single transaction.
+///
+/// A dispute can cover only part of the transaction and several partial disputes can be open at
+/// the same time, as long as the held, charged back and refunded amounts don't exceed the
+/// transaction amount.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransactionDispute {
+    /// Current state
+    state: DisputeState,
+    /// Number of times the transaction has been disputed
+    disputes: u32,
+    /// Amount of the disputed transaction
+    amount: Decimal,
+    /// Amount currently held by open disputes
+    held: Decimal,
+    /// Amount that has been charged back
+    charged_back: Decimal,
+    /// Amount that has been refunded
+    refunded: Decimal,
+    /// When the current dispute has been opened
+    opened_at: Option<Instant>,
+    /// States the transaction went through, in order
+    history: Vec<DisputeState>,
+}
+
+impl TransactionDispute {
+    pub fn new(amount: Decimal) -> Self {
+        Self {
+            state: DisputeState::Settled,
+            disputes: 0,
+            amount,
+            held: Decimal::ZERO,
+            charged_back: Decimal::ZERO,
+            refunded: Decimal::ZERO,
+            opened_at: None,
+            history: vec![DisputeState::Settled],
+        }
+    }
+
+    pub fn state(&self) -> DisputeState {
+        self.state
+    }
+
+    pub fn history(&self) -> &[DisputeState] {
+        &self.history
+    }
+
+    /// Amount of the disputed transaction
+    pub fn amount(&self) -> Decimal {
+        self.amount
+    }
+
+    /// Amount currently held by open disputes
+    pub fn held(&self) -> Decimal {
+        self.held
+    }
+
+    /// Amount that has been charged back
+    pub fn charged_back(&self) -> Decimal {
+        self.charged_back
+    }
+
+    /// Amount that has been refunded
+    pub fn refunded(&self) -> Decimal {
+        self.refunded
+    }
+
+    /// When the current, or the last, dispute has been opened
+    pub fn opened_at(&self) -> Option<Instant> {
+        self.opened_at
+    }
+
+    /// Amount of the transaction that can still be disputed or refunded
+    pub fn undisputed(&self) -> Decimal {
+        self.amount - self.held - self.charged_back - self.refunded
+    }
+
+    /// Dispute the given amount, or everything that is not disputed yet. Returns the amount that
+    /// has to be held.
+    pub fn open(
+        &mut self,
+        amount: Option<Decimal>,
+        policy: &DisputePolicy,
+        now: Instant,
+    ) -> Result<Decimal, EngineError> {
+        if self.state == DisputeState::ChargedBack {
+            return Err(EngineError::ChargedBack);
+        }
+
+        if self.state == DisputeState::Disputed && self.undisputed().is_zero() {
+            return Err(EngineError::AlreadyDisputed);
+        }
+
+        let amount = check_amount(amount.unwrap_or(self.undisputed()), self.undisputed())?;
+
+        // Disputing more of an already disputed transaction doesn't open a new dispute
+        if self.state != DisputeState::Disputed {
+            if policy
+                .max_disputes
+                .is_some_and(|max_disputes| self.disputes >= max_disputes)
+            {
+                return Err(EngineError::MaxDisputesReached);
+            }
+
+            self.disputes += 1;
+            self.opened_at = Some(now);
+            self.transition(DisputeState::Disputed);
+        }
+
+        self.held += amount;
+
+        Ok(amount)
+    }
+
+    /// Release the given amount, or everything that is held, in favour of the client. The dispute
+    /// is resolved once nothing is held anymore. Returns the released amount.
+    pub fn resolve(&mut self, amount: Option<Decimal>) -> Result<Decimal, EngineError> {
+        self.check_disputed()?;
+
+        let amount = check_amount(amount.unwrap_or(self.held), self.held)?;
+
+        self.held -= amount;
+        if self.held.is_zero() {
+            self.transition(DisputeState::Resolved);
+        }
+
+        Ok(amount)
+    }
+
+    /// Reverse the given amount, or everything that is held. This closes the dispute, anything
+    /// else that is held has to be released. Returns the charged back amount.
+    pub fn chargeback(&mut self, amount: Option<Decimal>) -> Result<Decimal, EngineError> {
+        self.check_disputed()?;
+
+        let amount = check_amount(amount.unwrap_or(self.held), self.held)?;
+
+        self.held = Decimal::ZERO;
+        self.charged_back += amount;
+        self.transition(DisputeState::ChargedBack);
+
+        Ok(amount)
+    }
+
+    /// Refund the given amount, or everything that is neither disputed nor reversed yet. The
+    /// amount under an open dispute can't be refunded. Returns the refunded amount.
+    pub fn refund(&mut self, amount: Option<Decimal>) -> Result<Decimal, EngineError> {
+        let amount = amount.unwrap_or(self.undisputed());
+
+        if amount <= Decimal::ZERO {
+            return Err(EngineError::NonPositiveAmount);
+        }
+
+        if amount > self.undisputed() {
+            return Err(EngineError::ExceedsRefundable);
+        }
+
+        self.refunded += amount;
+
+        Ok(amount)
+    }
+
+    fn check_disputed(&self) -> Result<(), EngineError> {
+        if self.state != DisputeState::Disputed {
+            return Err(EngineError::NotDisputed);
+        }
+
+        Ok(())
+    }
+
+    fn transition(&mut self, state: DisputeState) {
+        self.state = state;
+        self.history.push(state);
+    }
+}
+
+/// Check that the amount is positive and doesn't exceed the given maximum
+fn check_amount(amount: Decimal, max: Decimal) -> Result<Decimal, EngineError> {
+    if amount <= Decimal::ZERO {
+        return Err(EngineError::NonPositiveAmount);
+    }
+
+    if amount > max {
+        return Err(EngineError::ExceedsDisputed);
+    }
+
+    Ok(amount)
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+
+    use crate::clock::Instant;
+
+    use super::{DisputePolicy, DisputeState, TransactionDispute};
+
+    #[test]
+    fn test_dispute_lifecycle() {
+        let policy = DisputePolicy {
+            max_disputes: Some(2),
+            ..Default::default()
+        };
+        let mut dispute = TransactionDispute::new(Decimal::new(10, 0));
+        assert_eq!(dispute.state(), DisputeState::Settled);
+
+        // only disputed transactions can be resolved or charged back
+        assert!(dispute.resolve(None).is_err());
+        assert!(dispute.chargeback(None).is_err());
+
+        dispute
+            .open(None, &policy, Instant::default())
+            .expect("Could not open dispute.");
+        assert!(dispute.open(None, &policy, Instant::default()).is_err());
+        dispute.resolve(None).expect("Could not resolve dispute.");
+
+        dispute
+            .open(None, &policy, Instant::default())
+            .expect("Could not open second dispute.");
+        dispute.resolve(None).expect("Could not resolve dispute.");
+
+        // maximum number of disputes reached
+        assert!(dispute.open(None, &policy, Instant::default()).is_err());
+
+        assert_eq!(
+            dispute.history(),
+            [
+                DisputeState::Settled,
+                DisputeState::Disputed,
+                DisputeState::Resolved,
+                DisputeState::Disputed,
+                DisputeState::Resolved,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_chargeback_is_terminal() {
+        let policy = DisputePolicy {
+            max_disputes: None,
+            ..Default::default()
+        };
+        let mut dispute = TransactionDispute::new(Decimal::new(10, 0));
+
+        dispute
+            .open(None, &policy, Instant::default())
+            .expect("Could not open dispute.");
+        dispute.chargeback(None).expect("Could not chargeback.");
+
+        assert_eq!(dispute.state(), DisputeState::ChargedBack);
+        assert!(dispute.open(None, &policy, Instant::default()).is_err());
+        assert!(dispute.resolve(None).is_err());
+        assert!(dispute.chargeback(None).is_err());
+    }
+
+    #[test]
+    fn test_partial_dispute() {
+        let policy = DisputePolicy::default();
+        let mut dispute = TransactionDispute::new(Decimal::new(10, 0));
+
+        assert_eq!(
+            dispute.open(Some(Decimal::new(3, 0)), &policy, Instant::default()),
+            Ok(Decimal::new(3, 0))
+        );
+        // a second partial dispute extends the open dispute
+        assert_eq!(
+            dispute.open(Some(Decimal::new(5, 0)), &policy, Instant::default()),
+            Ok(Decimal::new(5, 0))
+        );
+        assert_eq!(dispute.held(), Decimal::new(8, 0));
+
+        // disputes can't exceed the transaction amount
+        assert!(
+            dispute
+                .open(Some(Decimal::new(3, 0)), &policy, Instant::default())
+                .is_err()
+        );
+        assert!(
+            dispute
+                .open(Some(Decimal::new(-1, 0)), &policy, Instant::default())
+                .is_err()
+        );
+
+        assert_eq!(
+            dispute.resolve(Some(Decimal::new(2, 0))),
+            Ok(Decimal::new(2, 0))
+        );
+        assert_eq!(dispute.state(), DisputeState::Disputed);
+        assert!(dispute.resolve(Some(Decimal::new(7, 0))).is_err());
+
+        // the remaining held amount is released by the chargeback
+        assert_eq!(
+            dispute.chargeback(Some(Decimal::new(4, 0))),
+            Ok(Decimal::new(4, 0))
+        );
+        assert_eq!(dispute.state(), DisputeState::ChargedBack);
+        assert_eq!(dispute.held(), Decimal::ZERO);
+        assert_eq!(dispute.charged_back(), Decimal::new(4, 0));
+        assert_eq!(dispute.undisputed(), Decimal::new(6, 0));
+    }
+}
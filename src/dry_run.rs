@@ -0,0 +1,199 @@
+use std::io::{Read, Write};
+
+use serde_json::{Value, json};
+
+use crate::{
+    diff::{StateDiff, state},
+    engine::TransactionsEngine,
+    error::TransactionError,
+    ids::{ClientId, TxId},
+    input::TransactionReader,
+};
+
+/// Row of the input that would be rejected.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rejection {
+    pub line: u64,
+    /// Client and transaction id, when the row could be read
+    pub ids: Option<(ClientId, TxId)>,
+    pub error: TransactionError,
+}
+
+/// Projected outcome of processing an input, without keeping any of it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DryRun {
+    /// Number of rows of the input
+    pub rows: u64,
+    /// Rows that would be rejected, in the order of the input
+    pub rejections: Vec<Rejection>,
+    /// Accounts that would change
+    pub diff: StateDiff,
+}
+
+impl DryRun {
+    /// Read every row of the CSV input and simulate it on the engine, which is dropped afterwards.
+    /// The engine can be a new one or one restored from a snapshot.
+    pub fn run<R: Read>(mut engine: TransactionsEngine, reader: R) -> Self {
+        let before = state(&engine);
+        let mut rows = 0;
+        let mut rejections = Vec::new();
+
+        for line in TransactionReader::new(reader) {
+            rows += 1;
+            let input = match line {
+                Ok(input) => input,
+                Err(err) => {
+                    rejections.push(Rejection {
+                        line: err.line,
+                        ids: None,
+                        error: err.error.into(),
+                    });
+                    continue;
+                }
+            };
+
+            let ids = input.transaction.ids();
+            if let Err(err) = engine.process_transaction_at(input.transaction, input.timestamp) {
+                rejections.push(Rejection {
+                    line: input.line,
+                    ids,
+                    error: err.into(),
+                });
+            }
+        }
+
+        Self {
+            rows,
+            rejections,
+            diff: StateDiff::between(&before, &state(&engine)),
+        }
+    }
+
+    pub fn accepted(&self) -> u64 {
+        self.rows - self.rejections.len() as u64
+    }
+
+    /// Whether every row would be accepted
+    pub fn is_clean(&self) -> bool {
+        self.rejections.is_empty()
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "rows": self.rows,
+            "accepted": self.accepted(),
+            "rejected": self.rejections.len(),
+            "rejections": self.rejections.iter().map(|rejection| json!({
+                "line": rejection.line,
+                "client": rejection.ids.map(|(client, _)| client),
+                "tx": rejection.ids.map(|(_, tx)| tx.to_string()),
+                "kind": rejection.error.kind(),
+                "reason": rejection.error.to_string(),
+            })).collect::<Vec<_>>(),
+            "diff": self.diff.to_json(),
+        })
+    }
+
+    /// Write the rejected rows, a summary and the changes of the accounts as CSV
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<(), String> {
+        let mut report = String::new();
+        for rejection in &self.rejections {
+            report += &format!("Line {}", rejection.line);
+            if let Some((client, tx)) = rejection.ids {
+                report += &format!(" (client {client}, tx {tx})");
+            }
+            report += &format!(": {}: {}\n", rejection.error.kind(), rejection.error);
+        }
+        report += &format!(
+            "{} rows, {} accepted, {} rejected\n\n",
+            self.rows,
+            self.accepted(),
+            self.rejections.len()
+        );
+
+        writer
+            .write_all(report.as_bytes())
+            .map_err(|err| format!("Could not write report: {err:?}"))?;
+        self.diff.write(writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+
+    use crate::{
+        diff::AccountState,
+        engine::TransactionsEngine,
+        ids::{ClientId, TxId},
+        types::TransactionType,
+    };
+
+    use super::DryRun;
+
+    #[test]
+    fn test_dry_run() {
+        let mut engine = TransactionsEngine::new();
+        engine
+            .process_transaction(TransactionType::Deposit {
+                client: ClientId(1),
+                tx: TxId::from(1),
+                amount: Decimal::new(10, 0),
+                currency: None,
+            })
+            .expect("Could not process deposit.");
+
+        let input = "type,client,tx,amount\n\
+            withdrawal,1,2,4.0\n\
+            withdrawal,1,3,20.0\n\
+            deposit,2,abc,1.0\n\
+            deposit,2,4,1.5\n";
+        let dry_run = DryRun::run(engine, input.as_bytes());
+
+        assert_eq!(dry_run.rows, 4);
+        assert_eq!(dry_run.accepted(), 2);
+        assert_eq!(dry_run.rejections[0].line, 3);
+        assert_eq!(
+            dry_run.rejections[0].ids,
+            Some((ClientId(1), TxId::from(3)))
+        );
+        assert_eq!(dry_run.rejections[0].error.kind(), "insufficient_funds");
+        assert_eq!(dry_run.rejections[1].error.kind(), "invalid_id");
+
+        assert_eq!(dry_run.diff.changes.len(), 2);
+        assert_eq!(
+            dry_run.diff.changes[0]
+                .before
+                .as_ref()
+                .map(|account| account.total),
+            Some(Decimal::new(10, 0))
+        );
+        assert_eq!(
+            dry_run.diff.changes[0]
+                .after
+                .as_ref()
+                .map(|account| account.total),
+            Some(Decimal::new(60, 1))
+        );
+        assert_eq!(dry_run.diff.changes[1].before, None);
+        assert_eq!(
+            dry_run.diff.changes[1].after,
+            Some(AccountState {
+                available: Decimal::new(15, 1),
+                held: Decimal::ZERO,
+                total: Decimal::new(15, 1),
+                locked: false,
+            })
+        );
+
+        let mut output = Vec::new();
+        dry_run.write(&mut output).expect("Could not write report.");
+        let output = String::from_utf8(output).expect("Invalid output.");
+        assert!(output.starts_with(
+            "Line 3 (client 1, tx 3): insufficient_funds: Insufficient funds\n\
+             Line 4: invalid_id: Invalid tx id \"abc\": malformed\n\
+             4 rows, 2 accepted, 2 rejected\n"
+        ));
+        assert!(output.ends_with("added,2,,,1.5,,0,,1.5,,false\n"));
+    }
+}
@@ -1,96 +1,1145 @@
 use csv::Writer;
-use tracing::error;
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde_json::{Value, json};
+use tracing::{error, info};
 
-use crate::{client::Client, types::TransactionType};
-use std::collections::HashMap;
+use crate::{
+    authorization::AuthorizationState,
+    client::Client,
+    clock::{Clock, Instant},
+    config::EngineConfig,
+    conversion::{RateTable, convert},
+    currency::{Balance, Currency},
+    dispute::{DisputeState, ExpiryAction},
+    error::EngineError,
+    event::{EngineEvent, EventKind},
+    fees::FeeType,
+    ids::{ClientId, TxId},
+    ledger::{Account, EntryKind, Ledger, LedgerEntry},
+    limits::{Limit, LimitMode},
+    output::{OutputFormat, OutputOrder, markdown_table},
+    registry::{AccountStatus, Registry},
+    risk::{Alert, RiskRule, RiskRules, Verdict},
+    snapshot::Snapshot,
+    types::{TransactionType, check_precision},
+};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, VecDeque},
+    io::Write,
+};
 
 /// Transactions engine that helps with processing the transactions.
 pub struct TransactionsEngine {
-    clients: HashMap<u16, Client>,
+    clients: HashMap<ClientId, Client>,
+    config: EngineConfig,
+    clock: Clock,
+    /// Open disputes (opening instant, client id, transaction id) in the order they were opened,
+    /// only tracked when disputes expire
+    open_disputes: VecDeque<(Instant, ClientId, TxId)>,
+    /// Pending authorizations (authorization instant, client id, transaction id) in the order
+    /// they were made, only tracked when authorizations expire
+    open_authorizations: VecDeque<(Instant, ClientId, TxId)>,
+    /// Actions taken by the engine on its own
+    events: Vec<EngineEvent>,
+    /// Lines posted to the client and house accounts
+    ledger: Ledger,
+    /// Rates used to convert between currencies
+    rates: RateTable,
+    /// Rules run before each transaction is applied
+    risk_rules: RiskRules,
+    /// Alerts raised by the risk rules
+    alerts: Vec<Alert>,
+    /// Day of the latest timestamped transaction, interest is accrued when it changes
+    last_day: Option<u64>,
+    /// Registered accounts
+    registry: Registry,
+}
+
+impl Default for TransactionsEngine {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl TransactionsEngine {
     pub fn new() -> Self {
+        Self::with_config(EngineConfig::default())
+    }
+
+    pub fn with_config(config: EngineConfig) -> Self {
         Self {
+            risk_rules: RiskRules::from_config(&config.risk_rules),
+            alerts: Vec::new(),
+            last_day: None,
+            registry: Registry::default(),
             clients: HashMap::new(),
+            config,
+            clock: Clock::default(),
+            open_disputes: VecDeque::new(),
+            open_authorizations: VecDeque::new(),
+            events: Vec::new(),
+            ledger: Ledger::default(),
+            rates: RateTable::default(),
+        }
+    }
+
+    /// Use the given rates to convert between currencies
+    pub fn with_rates(mut self, rates: RateTable) -> Self {
+        self.rates = rates;
+        self
+    }
+
+    /// Open the accounts of the registry, with their status, currency, tier and limits
+    pub fn with_registry(mut self, registry: Registry) -> Self {
+        for account in registry.accounts() {
+            let limits = &mut self.config.limits;
+            if let Some(tier) = &account.tier {
+                limits.client_tiers.insert(account.client, tier.clone());
+            }
+            if account.has_limits() {
+                let client_limits = account.limits(limits.limits(account.client));
+                limits.clients.insert(account.client, client_limits);
+            }
+
+            let mut client = new_client(account.client, &self.config);
+            client.locked = account.status == Some(AccountStatus::Locked);
+            client.currency = account.currency.clone();
+            self.clients.insert(account.client, client);
+        }
+
+        self.registry = registry;
+        self
+    }
+
+    /// Resume from the state of the snapshot, replacing the accounts opened so far
+    pub fn with_snapshot(mut self, snapshot: Snapshot) -> Self {
+        self.clock = Clock::starting_at(snapshot.now);
+        self.last_day = snapshot.last_day;
+        self.clients = snapshot
+            .clients
+            .into_iter()
+            .map(|client| (client.client, client))
+            .collect();
+        self.open_disputes = snapshot.open_disputes.into();
+        self.open_authorizations = snapshot.open_authorizations.into();
+        self.events = snapshot.events;
+        self.ledger = snapshot.ledger;
+        self.alerts = snapshot.alerts;
+        self
+    }
+
+    /// Current state of the engine
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            now: self.clock.now(),
+            last_day: self.last_day,
+            clients: self.clients().into_iter().cloned().collect(),
+            open_disputes: self.open_disputes.iter().copied().collect(),
+            open_authorizations: self.open_authorizations.iter().copied().collect(),
+            events: self.events.clone(),
+            ledger: self.ledger.clone(),
+            alerts: self.alerts.clone(),
         }
     }
 
+    /// Run the given risk rule after the configured ones
+    pub fn with_risk_rule(mut self, rule: Box<dyn RiskRule>) -> Self {
+        self.risk_rules = self.risk_rules.with_rule(rule);
+        self
+    }
+
+    /// Alerts raised by the risk rules, in the order they happened
+    pub fn alerts(&self) -> &[Alert] {
+        &self.alerts
+    }
+
+    /// Ledger with the lines posted by the engine
+    pub fn ledger(&self) -> &Ledger {
+        &self.ledger
+    }
+
+    /// Actions taken by the engine on its own, in the order they happened
+    pub fn events(&self) -> &[EngineEvent] {
+        &self.events
+    }
+
+    /// Instant of the last processed transaction
+    pub fn now(&self) -> Instant {
+        self.clock.now()
+    }
+
+    /// Account of the given client, if it has been opened
+    pub fn client(&self, client: ClientId) -> Option<&Client> {
+        self.clients.get(&client)
+    }
+
+    /// Accounts of all the clients, by client id
+    pub fn clients(&self) -> Vec<&Client> {
+        let mut clients: Vec<_> = self.clients.values().collect();
+        clients.sort_by_key(|client| client.client);
+        clients
+    }
+
+    /// Dispute states the given transaction went through, if it has been processed for the client
+    pub fn dispute_history(&self, client: ClientId, tx: TxId) -> Option<&[DisputeState]> {
+        self.clients.get(&client)?.dispute_history(tx)
+    }
+
     /// Process a given transaction
-    pub fn process_transaction(&mut self, transaction: TransactionType) -> Result<(), String> {
+    pub fn process_transaction(&mut self, transaction: TransactionType) -> Result<(), EngineError> {
+        self.process_transaction_at(transaction, None)
+    }
+
+    /// Process a given transaction that happened at the given unix timestamp, if known
+    pub fn process_transaction_at(
+        &mut self,
+        transaction: TransactionType,
+        timestamp: Option<u64>,
+    ) -> Result<(), EngineError> {
+        let now = self.clock.tick(timestamp);
+        self.roll_over(now);
+        self.expire_disputes(now);
+        self.expire_authorizations(now);
+
+        if let Some((client, _)) = transaction.ids() {
+            self.check_registered(client)?;
+        }
+        if let TransactionType::Transfer { destination, .. } = transaction {
+            self.check_registered(destination)?;
+        }
+
+        // Clients can have their own default currency
+        let default_currency = transaction
+            .ids()
+            .and_then(|(client, _)| self.clients.get(&client)?.currency.clone())
+            .unwrap_or_else(|| self.config.default_currency.clone());
+        let transaction = transaction.with_default_currency(&default_currency);
+
+        let verdicts = self.check_risk(&transaction, now)?;
+        let ids = transaction.ids();
+
+        self.apply_transaction(transaction, now)?;
+
+        if let Some((client, tx)) = ids {
+            self.raise_alerts(client, tx, verdicts, now);
+        }
+
+        Ok(())
+    }
+
+    /// Check that the client is registered, when the transactions of unregistered clients are
+    /// rejected
+    fn check_registered(&self, client: ClientId) -> Result<(), EngineError> {
+        if self.config.registry.reject_unregistered && !self.registry.contains(client) {
+            return Err(EngineError::ClientNotRegistered);
+        }
+
+        Ok(())
+    }
+
+    /// Run the risk rules on the transaction, with the current state of its client
+    fn check_risk(
+        &self,
+        transaction: &TransactionType,
+        now: Instant,
+    ) -> Result<Vec<(String, Verdict)>, EngineError> {
+        let Some((client_id, _)) = transaction.ids() else {
+            return Ok(Vec::new());
+        };
+
+        match self.clients.get(&client_id) {
+            Some(client) => self.risk_rules.check(transaction, client, now),
+            None => self
+                .risk_rules
+                .check(transaction, &new_client(client_id, &self.config), now),
+        }
+    }
+
+    /// Record the alerts raised by the risk rules for an applied transaction, locking the account
+    /// if any rule asked for it
+    fn raise_alerts(
+        &mut self,
+        client_id: ClientId,
+        tx: TxId,
+        verdicts: Vec<(String, Verdict)>,
+        now: Instant,
+    ) {
+        for (rule, verdict) in verdicts {
+            let (reason, locked) = match verdict {
+                Verdict::Flag(reason) => (reason, false),
+                Verdict::Lock(reason) => (reason, true),
+                Verdict::Allow | Verdict::Reject(_) => continue,
+            };
+
+            if locked && let Some(client) = self.clients.get_mut(&client_id) {
+                info!("Account locked by {rule} - client {client_id}: {reason}");
+                client.locked = true;
+            }
+
+            self.alerts.push(Alert {
+                at: now,
+                rule,
+                client: client_id,
+                tx,
+                locked,
+                reason,
+            });
+        }
+    }
+
+    /// Apply a transaction to the accounts
+    fn apply_transaction(
+        &mut self,
+        transaction: TransactionType,
+        now: Instant,
+    ) -> Result<(), EngineError> {
         match transaction {
-            TransactionType::Deposit { client, tx, amount } => {
-                let client = self.clients.entry(client).or_insert(Client::new(client));
+            TransactionType::Deposit {
+                client: client_id,
+                tx,
+                amount,
+                ref currency,
+            } => {
+                let currency = currency.clone().unwrap_or_default();
+                let precision = self.config.precision(&currency);
+                check_precision(amount, precision)?;
+
+                let fee = self.config.fees.fee(FeeType::Deposit, amount, precision);
+                let client = self
+                    .clients
+                    .entry(client_id)
+                    .or_insert_with(|| new_client(client_id, &self.config));
+
+                client.check_deposit(amount, tx, fee, &currency)?;
+
+                let breached = self.config.limits.limits(client_id).check_deposit(
+                    client.activity(),
+                    client.balance(&currency).total,
+                    amount,
+                    &currency,
+                    now,
+                );
+                self.enforce_limits(client_id, tx, amount, &currency, &breached)?;
+
+                if let Some(client) = self.clients.get_mut(&client_id) {
+                    client.deposit(amount, tx, &currency)?;
+                    client.record_transaction(tx, transaction, now);
+                }
+
+                self.charge_fee(client_id, tx, FeeType::Deposit, fee, &currency, now);
+            }
+            TransactionType::Withdrawal {
+                client: client_id,
+                tx,
+                amount,
+                ref currency,
+            } => {
+                let currency = currency.clone().unwrap_or_default();
+                let precision = self.config.precision(&currency);
+                check_precision(amount, precision)?;
+
+                let fee = self.config.fees.fee(FeeType::Withdrawal, amount, precision);
+                let client = self
+                    .clients
+                    .entry(client_id)
+                    .or_insert_with(|| new_client(client_id, &self.config));
+
+                // The part of the withdrawal and its fee not covered by the funds is drawn on the
+                // credit line
+                let drawn = (amount + fee - client.balance(&currency).available.max(Decimal::ZERO))
+                    .max(Decimal::ZERO);
+                let overdraft_fee = if drawn.is_zero() {
+                    Decimal::ZERO
+                } else {
+                    self.config.fees.fee(FeeType::Overdraft, drawn, precision)
+                };
+
+                client.check_withdrawal(amount, tx, fee + overdraft_fee, &currency)?;
+
+                let breached = self.config.limits.limits(client_id).check_withdrawal(
+                    client.activity(),
+                    amount,
+                    &currency,
+                    now,
+                );
+                self.enforce_limits(client_id, tx, amount, &currency, &breached)?;
+
+                if let Some(client) = self.clients.get_mut(&client_id) {
+                    client.withdrawal(amount, tx, &currency)?;
+                    client.record_transaction(tx, transaction, now);
+                }
+
+                self.charge_fee(client_id, tx, FeeType::Withdrawal, fee, &currency, now);
+                self.charge_fee(
+                    client_id,
+                    tx,
+                    FeeType::Overdraft,
+                    overdraft_fee,
+                    &currency,
+                    now,
+                );
+            }
+            TransactionType::Dispute {
+                client: client_id,
+                tx,
+                amount,
+            } => {
+                self.check_dispute_precision(client_id, tx, amount)?;
+
+                let client = self
+                    .clients
+                    .get_mut(&client_id)
+                    .ok_or(EngineError::ClientNotFound)?;
 
-                client.deposit(amount, tx)?;
-                client.processed_transactions.insert(tx, transaction);
+                client.dispute(tx, amount, &self.config.dispute, now)?;
+
+                // Keep track of the newly opened disputes so they can expire
+                let opened_at = client
+                    .transaction_dispute(tx)
+                    .and_then(|dispute| dispute.opened_at());
+                if self.config.dispute.expiry.is_some() && opened_at == Some(now) {
+                    self.open_disputes.push_back((now, client_id, tx));
+                }
             }
-            TransactionType::Withdrawal { client, tx, amount } => {
-                let client = self.clients.entry(client).or_insert(Client::new(client));
+            TransactionType::Resolve { client, tx, amount } => {
+                self.check_dispute_precision(client, tx, amount)?;
 
-                client.withdrawal(amount, tx)?;
-                client.processed_transactions.insert(tx, transaction);
+                self.clients
+                    .get_mut(&client)
+                    .ok_or(EngineError::ClientNotFound)?
+                    .resolve(tx, amount)?
             }
-            TransactionType::Dispute { client, tx } => self
-                .clients
-                .entry(client)
-                .or_insert(Client::new(client))
-                .dispute(tx)?,
-            TransactionType::Resolve { client, tx } => self
-                .clients
-                .entry(client)
-                .or_insert(Client::new(client))
-                .resolve(tx)?,
-            TransactionType::Chargeback { client, tx } => self
+            TransactionType::Transfer {
+                client: source,
+                destination,
+                tx,
+                amount,
+                ref currency,
+            } => {
+                let currency = currency.clone().unwrap_or_default();
+                let precision = self.config.precision(&currency);
+                check_precision(amount, precision)?;
+
+                let fee = self.config.fees.fee(FeeType::Transfer, amount, precision);
+
+                self.transfer(source, destination, tx, amount, fee, &currency)?;
+
+                for client in [source, destination] {
+                    if let Some(client) = self.clients.get_mut(&client) {
+                        client.record_transaction(tx, transaction.clone(), now);
+                    }
+                }
+
+                self.charge_fee(source, tx, FeeType::Transfer, fee, &currency, now);
+            }
+            TransactionType::Chargeback { client, tx, amount } => {
+                self.check_dispute_precision(client, tx, amount)?;
+                self.chargeback(client, tx, amount, now)?;
+            }
+            TransactionType::Authorize {
+                client: client_id,
+                tx,
+                amount,
+                ref currency,
+            } => {
+                let currency = currency.clone().unwrap_or_default();
+                check_precision(amount, self.config.precision(&currency))?;
+
+                let client = self
+                    .clients
+                    .entry(client_id)
+                    .or_insert_with(|| new_client(client_id, &self.config));
+
+                client.authorize(amount, tx, &currency, now)?;
+                client.record_transaction(tx, transaction, now);
+
+                if self.config.authorization.expiry.is_some() {
+                    self.open_authorizations.push_back((now, client_id, tx));
+                }
+            }
+            TransactionType::Capture { client, tx, amount } => {
+                self.check_dispute_precision(client, tx, amount)?;
+
+                self.clients
+                    .get_mut(&client)
+                    .ok_or(EngineError::ClientNotFound)?
+                    .capture(tx, amount)?
+            }
+            TransactionType::Refund { client, tx, amount } => {
+                self.check_dispute_precision(client, tx, amount)?;
+
+                self.clients
+                    .get_mut(&client)
+                    .ok_or(EngineError::ClientNotFound)?
+                    .refund(tx, amount)?
+            }
+            TransactionType::Void { client, tx } => self
                 .clients
-                .entry(client)
-                .or_insert(Client::new(client))
-                .chargeback(tx)?,
-            _ => (),
+                .get_mut(&client)
+                .ok_or(EngineError::ClientNotFound)?
+                .void(tx)?,
+            TransactionType::Convert {
+                client: client_id,
+                tx,
+                amount,
+                ref currency,
+                ref to_currency,
+            } => {
+                let currency = currency.clone().unwrap_or_default();
+                check_precision(amount, self.config.precision(&currency))?;
+
+                if currency == *to_currency {
+                    return Err(EngineError::SameCurrencyConversion);
+                }
+
+                let rate = self
+                    .rates
+                    .rate(&currency, to_currency, now.timestamp)
+                    .ok_or_else(|| EngineError::NoConversionRate {
+                        from: currency.clone(),
+                        to: to_currency.clone(),
+                    })?;
+                let conversion = convert(
+                    amount,
+                    rate,
+                    self.config.conversion.spread_percent,
+                    self.config.precision(to_currency),
+                );
+
+                let client = self
+                    .clients
+                    .entry(client_id)
+                    .or_insert_with(|| new_client(client_id, &self.config));
+
+                client.convert(tx, amount, &currency, conversion.credited, to_currency)?;
+                client.record_transaction(tx, transaction.clone(), now);
+
+                if !conversion.revenue.is_zero() {
+                    self.ledger.post(LedgerEntry {
+                        at: now,
+                        tx: Some(tx),
+                        account: Account::House,
+                        kind: EntryKind::ConversionSpread,
+                        amount: conversion.revenue,
+                        currency: to_currency.clone(),
+                    });
+                }
+            }
+            TransactionType::Accrue => self.accrue_interest(now),
+            TransactionType::Unknown => (),
         }
 
         Ok(())
     }
 
-    /// Serialize and print current state
-    pub fn print_current_state(&self) {
-        let mut writer = Writer::from_writer(std::io::stdout());
+    /// Accrue a day of interest for each day that passed since the previous timestamped
+    /// transaction
+    fn roll_over(&mut self, now: Instant) {
+        let Some(day) = now.day() else {
+            return;
+        };
+
+        if let Some(last_day) = self.last_day {
+            for _ in last_day..day {
+                self.accrue_interest(now);
+            }
+        }
+
+        self.last_day = Some(day);
+    }
+
+    /// Accrue a day of interest on the available funds of each client, in each currency, and post
+    /// the interest that reaches the precision of the currency
+    fn accrue_interest(&mut self, now: Instant) {
+        let Some(interest) = &self.config.interest else {
+            return;
+        };
 
-        if let Err(err) = writer.write_record(["client", "available", "held", "total", "locked"]) {
-            error!("Could not write record: {err:?}");
+        // Post in client order so the ledger doesn't depend on the order of the accounts
+        let mut client_ids: Vec<_> = self.clients.keys().copied().collect();
+        client_ids.sort_unstable();
+
+        for client_id in client_ids {
+            let Some(client) = self.clients.get_mut(&client_id) else {
+                continue;
+            };
+
+            let balances: Vec<_> = client
+                .balances
+                .iter()
+                .map(|(currency, balance)| (currency.clone(), balance.available))
+                .collect();
+
+            for (currency, available) in balances {
+                let posted = client.accrue_interest(
+                    interest.daily_interest(available),
+                    &currency,
+                    self.config.precision(&currency),
+                );
+                if posted.is_zero() {
+                    continue;
+                }
+
+                for (account, amount) in [
+                    (Account::Client(client_id), posted),
+                    (Account::House, -posted),
+                ] {
+                    self.ledger.post(LedgerEntry {
+                        at: now,
+                        tx: None,
+                        account,
+                        kind: EntryKind::Interest,
+                        amount,
+                        currency: currency.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    /// Check the precision of the amount of a dispute, resolve, chargeback, capture or refund
+    /// against the currency of the transaction it refers to
+    fn check_dispute_precision(
+        &self,
+        client: ClientId,
+        tx: TxId,
+        amount: Option<Decimal>,
+    ) -> Result<(), EngineError> {
+        if let (Some(amount), Some(client)) = (amount, self.clients.get(&client)) {
+            check_precision(
+                amount,
+                self.config.precision(&client.transaction_currency(tx)),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Move funds between two clients. Both clients are checked before any of them is updated,
+    /// so either both sides of the transfer are applied or none of them. The source has to cover
+    /// the fee of the transfer as well.
+    fn transfer(
+        &mut self,
+        source: ClientId,
+        destination: ClientId,
+        tx: TxId,
+        amount: Decimal,
+        fee: Decimal,
+        currency: &Currency,
+    ) -> Result<(), EngineError> {
+        if source == destination {
+            return Err(EngineError::SelfTransfer);
+        }
+
+        // Neither account is opened before both sides have been checked. Money arriving by
+        // transfer is subject to the deposit limits of the destination.
+        let receiver = self.client_or_new(destination);
+        receiver.check_deposit(amount, tx, Decimal::ZERO, currency)?;
+
+        let deposit_breached = self.config.limits.limits(destination).check_deposit(
+            receiver.activity(),
+            receiver.balance(currency).total,
+            amount,
+            currency,
+            self.clock.now(),
+        );
+
+        // Money leaving by transfer is subject to the withdrawal limits of the source
+        let sender = self.client_or_new(source);
+        sender.check_withdrawal(amount, tx, fee, currency)?;
+
+        let withdrawal_breached = self.config.limits.limits(source).check_withdrawal(
+            sender.activity(),
+            amount,
+            currency,
+            self.clock.now(),
+        );
+
+        self.enforce_limits(source, tx, amount, currency, &withdrawal_breached)?;
+        self.enforce_limits(destination, tx, amount, currency, &deposit_breached)?;
+
+        self.clients
+            .entry(source)
+            .or_insert_with(|| new_client(source, &self.config))
+            .withdrawal(amount, tx, currency)?;
+        self.clients
+            .entry(destination)
+            .or_insert_with(|| new_client(destination, &self.config))
+            .deposit(amount, tx, currency)?;
+
+        Ok(())
+    }
+
+    /// Account of the client, or the account it would be opened with to check a transaction
+    /// before opening it
+    fn client_or_new(&self, client: ClientId) -> Cow<'_, Client> {
+        self.clients.get(&client).map_or_else(
+            || Cow::Owned(new_client(client, &self.config)),
+            Cow::Borrowed,
+        )
+    }
+
+    /// Reject a transaction of the client breaching any limit, or flag it with an event for each
+    /// breached limit when limits are not enforced
+    fn enforce_limits(
+        &mut self,
+        client: ClientId,
+        tx: TxId,
+        amount: Decimal,
+        currency: &Currency,
+        breached: &[Limit],
+    ) -> Result<(), EngineError> {
+        match (self.config.limits.mode, breached.first()) {
+            (_, None) => Ok(()),
+            (LimitMode::Hard, Some(limit)) => Err(EngineError::LimitExceeded(*limit)),
+            (LimitMode::Soft, Some(_)) => {
+                let at = self.clock.now();
+                self.events.extend(breached.iter().map(|limit| EngineEvent {
+                    at,
+                    kind: EventKind::LimitExceeded(*limit),
+                    client,
+                    tx,
+                    amount,
+                    currency: currency.clone(),
+                }));
+
+                Ok(())
+            }
         }
+    }
+
+    /// Charge back a disputed transaction. The funds of a transfer are returned to the source
+    /// client.
+    fn chargeback(
+        &mut self,
+        client_id: ClientId,
+        tx: TxId,
+        amount: Option<Decimal>,
+        now: Instant,
+    ) -> Result<(), EngineError> {
+        let client = self
+            .clients
+            .get_mut(&client_id)
+            .ok_or(EngineError::ClientNotFound)?;
+
+        let charged_back = client.chargeback(tx, amount)?;
+        let currency = client.transaction_currency(tx);
+
+        if let Some(&TransactionType::Transfer { client: source, .. }) =
+            client.processed_transactions.get(&tx)
+            && let Some(source) = self.clients.get_mut(&source)
+        {
+            source.return_transfer(charged_back, tx, &currency);
+        }
+
+        let fee = self.config.fees.fee(
+            FeeType::Chargeback,
+            charged_back,
+            self.config.precision(&currency),
+        );
+        self.charge_fee(client_id, tx, FeeType::Chargeback, fee, &currency, now);
+
+        Ok(())
+    }
+
+    /// Charge a fee to the client and post it to the house account
+    fn charge_fee(
+        &mut self,
+        client_id: ClientId,
+        tx: TxId,
+        fee_type: FeeType,
+        fee: Decimal,
+        currency: &Currency,
+        now: Instant,
+    ) {
+        if fee.is_zero() {
+            return;
+        }
+
+        let Some(client) = self.clients.get_mut(&client_id) else {
+            return;
+        };
+        client.charge_fee(fee, tx, currency);
+
+        for (account, amount) in [(Account::Client(client_id), -fee), (Account::House, fee)] {
+            self.ledger.post(LedgerEntry {
+                at: now,
+                tx: Some(tx),
+                account,
+                kind: EntryKind::Fee(fee_type),
+                amount,
+                currency: currency.clone(),
+            });
+        }
+    }
+
+    /// Close the disputes that have been open for longer than the configured expiry
+    fn expire_disputes(&mut self, now: Instant) {
+        let Some(expiry) = self.config.dispute.expiry else {
+            return;
+        };
+
+        while let Some(&(opened_at, client_id, tx)) = self.open_disputes.front() {
+            if !expiry.after.has_elapsed(opened_at, now) {
+                break;
+            }
+            self.open_disputes.pop_front();
+
+            let Some(client) = self.clients.get_mut(&client_id) else {
+                continue;
+            };
 
-        for client_data in self.clients.values() {
-            if let Err(err) = writer.write_record(&[
-                client_data.client.to_string(),
-                client_data.available.to_string(),
-                client_data.held.to_string(),
-                client_data.total.to_string(),
-                client_data.locked.to_string(),
-            ]) {
-                error!("Could not write record: {err:?}");
+            // The dispute might have been closed, or closed and opened again, in the meantime
+            let currency = client.transaction_currency(tx);
+            let Some(amount) = client
+                .transaction_dispute(tx)
+                .filter(|dispute| {
+                    dispute.state() == DisputeState::Disputed
+                        && dispute.opened_at() == Some(opened_at)
+                })
+                .map(|dispute| dispute.held())
+            else {
+                continue;
+            };
+
+            info!(
+                "Dispute expired - client {}, tx {}, action {:?}",
+                client_id, tx, expiry.action
+            );
+
+            let result = match expiry.action {
+                ExpiryAction::Resolve => client.resolve(tx, None),
+                ExpiryAction::Chargeback => self.chargeback(client_id, tx, None, now),
+            };
+
+            match result {
+                Ok(()) => self.events.push(EngineEvent {
+                    at: now,
+                    kind: EventKind::DisputeExpired(expiry.action),
+                    client: client_id,
+                    tx,
+                    amount,
+                    currency,
+                }),
+                Err(err) => error!(
+                    "Could not close expired dispute - client {}, tx {}: {err:?}",
+                    client_id, tx
+                ),
             }
         }
     }
+
+    /// Release the funds of the authorizations that have not been captured in time
+    fn expire_authorizations(&mut self, now: Instant) {
+        let Some(expiry) = self.config.authorization.expiry else {
+            return;
+        };
+
+        while let Some(&(authorized_at, client_id, tx)) = self.open_authorizations.front() {
+            if !expiry.has_elapsed(authorized_at, now) {
+                break;
+            }
+            self.open_authorizations.pop_front();
+
+            // The authorization might have been captured or voided in the meantime
+            let Some(client) = self.clients.get_mut(&client_id).filter(|client| {
+                client.authorization(tx).is_some_and(|authorization| {
+                    authorization.state() == AuthorizationState::Pending
+                })
+            }) else {
+                continue;
+            };
+
+            match client.expire_authorization(tx) {
+                Ok(amount) => self.events.push(EngineEvent {
+                    at: now,
+                    kind: EventKind::AuthorizationExpired,
+                    client: client_id,
+                    tx,
+                    amount,
+                    currency: client.transaction_currency(tx),
+                }),
+                Err(err) => error!(
+                    "Could not release expired authorization - client {}, tx {}: {err:?}",
+                    client_id, tx
+                ),
+            }
+        }
+    }
+
+    /// Serialize and write the events generated by the engine
+    pub fn write_events<W: Write>(&self, writer: W) -> Result<(), String> {
+        write_records(
+            writer,
+            EngineEvent::HEADER,
+            self.events.iter().map(EngineEvent::to_record),
+        )
+    }
+
+    /// Serialize and write the alerts raised by the risk rules
+    pub fn write_alerts<W: Write>(&self, writer: W) -> Result<(), String> {
+        write_records(
+            writer,
+            Alert::HEADER,
+            self.alerts.iter().map(Alert::to_record),
+        )
+    }
+
+    /// Serialize and write the lines posted to the ledger
+    pub fn write_ledger<W: Write>(&self, writer: W) -> Result<(), String> {
+        write_records(
+            writer,
+            LedgerEntry::HEADER,
+            self.ledger.entries().iter().map(LedgerEntry::to_record),
+        )
+    }
+
+    /// Serialize and write the total funds of each client converted to the given currency at the
+    /// latest rates, rounded half away from zero to the precision of the currency
+    pub fn write_valuation<W: Write>(&self, writer: W, currency: &Currency) -> Result<(), String> {
+        let precision = self.config.precision(currency);
+
+        let mut clients: Vec<_> = self.clients.values().collect();
+        clients.sort_by_key(|client| client.client);
+
+        let mut records = Vec::with_capacity(clients.len());
+        for client in clients {
+            let mut total = Decimal::ZERO;
+            for (from, balance) in &client.balances {
+                let rate = self
+                    .rates
+                    .rate(from, currency, None)
+                    .ok_or_else(|| format!("No conversion rate from {from} to {currency}"))?;
+                total += balance.total * rate;
+            }
+
+            records.push([
+                client.client.to_string(),
+                currency.to_string(),
+                total
+                    .round_dp_with_strategy(precision, RoundingStrategy::MidpointAwayFromZero)
+                    .to_string(),
+            ]);
+        }
+
+        write_records(writer, ["client", "currency", "total"], records.into_iter())
+    }
+
+    /// Serialize and print current state, in the configured format and order
+    pub fn print_current_state(&self) {
+        if let Err(err) = self.write_output(std::io::stdout()) {
+            error!("Could not write current state: {err}");
+        }
+    }
+
+    /// Serialize and write the current state, in the configured format and order
+    pub fn write_output<W: Write>(&self, writer: W) -> Result<(), String> {
+        let clients = match self.config.output_order {
+            OutputOrder::None => self.clients.values().collect(),
+            OutputOrder::Client => self.clients(),
+        };
+
+        match self.config.output_format {
+            OutputFormat::Csv => self.write_state(writer, clients.into_iter()),
+            OutputFormat::Json => self.write_state_json(writer, clients.into_iter()),
+            OutputFormat::Markdown => self.write_state_markdown(writer, clients.into_iter()),
+        }
+    }
+
+    /// Serialize and write the current state, one row per client and currency
+    pub fn write_current_state<W: Write>(&self, writer: W) -> Result<(), String> {
+        self.write_state(writer, self.clients.values())
+    }
+
+    /// Serialize and write the state of the given clients, one row per client and currency
+    pub fn write_state<'a, W: Write>(
+        &'a self,
+        writer: W,
+        clients: impl Iterator<Item = &'a Client>,
+    ) -> Result<(), String> {
+        write_records(writer, Self::STATE_HEADER, self.state_records(clients))
+    }
+
+    /// Serialize and write the state of the given clients as a Markdown table, one row per client
+    /// and currency
+    pub fn write_state_markdown<'a, W: Write>(
+        &'a self,
+        mut writer: W,
+        clients: impl Iterator<Item = &'a Client>,
+    ) -> Result<(), String> {
+        writer
+            .write_all(markdown_table(&Self::STATE_HEADER, self.state_records(clients)).as_bytes())
+            .map_err(|err| format!("Could not write state: {err:?}"))
+    }
+
+    /// Header of the state of the clients
+    const STATE_HEADER: [&str; 11] = [
+        "client",
+        "currency",
+        "available",
+        "held",
+        "reserved",
+        "total",
+        "locked",
+        "fees",
+        "credit_limit",
+        "credit_used",
+        "shortfall",
+    ];
+
+    /// Rows of the state of the given clients, in the order of the header
+    fn state_records<'a>(
+        &'a self,
+        clients: impl Iterator<Item = &'a Client>,
+    ) -> impl Iterator<Item = [String; 11]> {
+        clients.flat_map(|client| {
+            self.balances(client)
+                .into_iter()
+                .map(move |(currency, balance)| {
+                    [
+                        client.client.to_string(),
+                        currency.to_string(),
+                        balance.available.to_string(),
+                        balance.held.to_string(),
+                        balance.reserved.to_string(),
+                        balance.total.to_string(),
+                        client.locked.to_string(),
+                        balance.fees.to_string(),
+                        client.credit_limit.to_string(),
+                        balance.credit_used(client.credit_limit).to_string(),
+                        balance.shortfall(client.credit_limit).to_string(),
+                    ]
+                })
+        })
+    }
+
+    /// Serialize and write the state of the given clients as a JSON array, one object per client
+    /// and currency
+    pub fn write_state_json<'a, W: Write>(
+        &'a self,
+        mut writer: W,
+        clients: impl Iterator<Item = &'a Client>,
+    ) -> Result<(), String> {
+        let records: Vec<Value> = clients
+            .flat_map(|client| {
+                self.balances(client)
+                    .into_iter()
+                    .map(move |(currency, balance)| {
+                        json!({
+                            "client": client.client,
+                            "currency": currency.to_string(),
+                            "available": balance.available.to_string(),
+                            "held": balance.held.to_string(),
+                            "reserved": balance.reserved.to_string(),
+                            "total": balance.total.to_string(),
+                            "locked": client.locked,
+                            "fees": balance.fees.to_string(),
+                            "credit_limit": client.credit_limit.to_string(),
+                            "credit_used": balance.credit_used(client.credit_limit).to_string(),
+                            "shortfall": balance.shortfall(client.credit_limit).to_string(),
+                        })
+                    })
+            })
+            .collect();
+
+        serde_json::to_writer_pretty(&mut writer, &records)
+            .map_err(|err| format!("Could not write state: {err:?}"))?;
+        writeln!(writer).map_err(|err| format!("Could not write state: {err:?}"))
+    }
+
+    /// Funds of the client in each currency. Clients without any funds are shown in their default
+    /// currency.
+    pub fn balances<'a>(&'a self, client: &'a Client) -> Vec<(&'a Currency, Balance)> {
+        if client.balances.is_empty() {
+            let currency = client
+                .currency
+                .as_ref()
+                .unwrap_or(&self.config.default_currency);
+            return vec![(currency, Balance::default())];
+        }
+
+        client
+            .balances
+            .iter()
+            .map(|(currency, balance)| (currency, balance.clone()))
+            .collect()
+    }
+}
+
+/// New client account, with the credit limit configured for the client
+fn new_client(client: ClientId, config: &EngineConfig) -> Client {
+    let mut new_client = Client::new(client);
+    new_client.credit_limit = config
+        .limits
+        .limits(client)
+        .credit_limit
+        .unwrap_or_default();
+
+    new_client
+}
+
+/// Write the header and the records as CSV
+fn write_records<W: Write, const N: usize>(
+    writer: W,
+    header: [&str; N],
+    records: impl Iterator<Item = [String; N]>,
+) -> Result<(), String> {
+    let mut writer = Writer::from_writer(writer);
+
+    writer
+        .write_record(header)
+        .map_err(|err| format!("Could not write record: {err:?}"))?;
+
+    for record in records {
+        writer
+            .write_record(record)
+            .map_err(|err| format!("Could not write record: {err:?}"))?;
+    }
+
+    writer
+        .flush()
+        .map_err(|err| format!("Could not flush records: {err:?}"))
 }
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
+
     use rust_decimal::Decimal;
 
-    use crate::types::TransactionType;
+    use crate::{
+        authorization::AuthorizationPolicy,
+        clock::{Instant, Window},
+        config::EngineConfig,
+        conversion::{ConversionConfig, RateTable},
+        currency::{Currency, CurrencyConfig},
+        dispute::{DisputeExpiry, DisputePolicy, DisputeState, ExpiryAction},
+        error::EngineError,
+        event::{EngineEvent, EventKind},
+        fees::{Fee, FeeRule, FeeSchedule},
+        ids::{ClientId, TxId},
+        interest::{InterestConfig, InterestTier},
+        ledger::{Account, EntryKind},
+        limits::{CountLimit, Limit, LimitMode, Limits, LimitsConfig, VolumeLimit},
+        registry::{AccountStatus, RegisteredAccount, Registry, RegistryConfig},
+        risk::{DisputeRate, RuleConfig},
+        snapshot::Snapshot,
+        types::TransactionType,
+    };
 
     use super::TransactionsEngine;
 
     #[test]
     fn test_process_transaction() {
         let mut engine = TransactionsEngine::new();
-        let client_id = 1;
+        let client_id = ClientId(1);
 
         let deposit_tx = TransactionType::Deposit {
             client: client_id,
-            tx: 1,
+            tx: TxId::from(1),
             amount: Decimal::new(3, 0),
+            currency: None,
         };
         engine
             .process_transaction(deposit_tx)
@@ -98,24 +1147,27 @@ mod tests {
 
         let withdrawal_tx = TransactionType::Withdrawal {
             client: client_id,
-            tx: 2,
+            tx: TxId::from(2),
             amount: Decimal::new(2, 0),
+            currency: None,
         };
         engine
             .process_transaction(withdrawal_tx)
             .expect("Could not process withdrawal.");
 
         let client = engine.clients.get(&client_id).unwrap();
-        assert_eq!(client.available, Decimal::new(1, 0));
-        assert_eq!(client.total, Decimal::new(1, 0));
-        assert_eq!(client.held, Decimal::new(0, 0));
+        let balance = client.balance(&Currency::default());
+        assert_eq!(balance.available, Decimal::new(1, 0));
+        assert_eq!(balance.total, Decimal::new(1, 0));
+        assert_eq!(balance.held, Decimal::new(0, 0));
         assert!(!client.locked);
-        assert!(client.processed_transactions.contains_key(&1));
+        assert!(client.processed_transactions.contains_key(&TxId::from(1)));
 
         let deposit_tx = TransactionType::Deposit {
             client: client_id,
-            tx: 3,
+            tx: TxId::from(3),
             amount: Decimal::new(3, 0),
+            currency: None,
         };
         engine
             .process_transaction(deposit_tx.clone())
@@ -126,7 +1178,8 @@ mod tests {
 
         let resolve_tx = TransactionType::Resolve {
             client: client_id,
-            tx: 3,
+            tx: TxId::from(3),
+            amount: None,
         };
 
         // check that resolving an undisputed transaction fails
@@ -134,16 +1187,18 @@ mod tests {
 
         let dispute_tx = TransactionType::Dispute {
             client: client_id,
-            tx: 3,
+            tx: TxId::from(3),
+            amount: None,
         };
         engine
             .process_transaction(dispute_tx)
             .expect("Could not dispute transaction.");
 
         let client = engine.clients.get(&client_id).unwrap();
-        assert_eq!(client.available, Decimal::new(1, 0));
-        assert_eq!(client.total, Decimal::new(4, 0));
-        assert_eq!(client.held, Decimal::new(3, 0));
+        let balance = client.balance(&Currency::default());
+        assert_eq!(balance.available, Decimal::new(1, 0));
+        assert_eq!(balance.total, Decimal::new(4, 0));
+        assert_eq!(balance.held, Decimal::new(3, 0));
         assert!(!client.locked);
 
         engine
@@ -151,15 +1206,17 @@ mod tests {
             .expect("Could not resolve transaction");
 
         let client = engine.clients.get(&client_id).unwrap();
-        assert_eq!(client.available, Decimal::new(4, 0));
-        assert_eq!(client.total, Decimal::new(4, 0));
-        assert_eq!(client.held, Decimal::new(0, 0));
+        let balance = client.balance(&Currency::default());
+        assert_eq!(balance.available, Decimal::new(4, 0));
+        assert_eq!(balance.total, Decimal::new(4, 0));
+        assert_eq!(balance.held, Decimal::new(0, 0));
         assert!(!client.locked);
 
         let deposit_tx = TransactionType::Deposit {
             client: client_id,
-            tx: 4,
+            tx: TxId::from(4),
             amount: Decimal::new(1, 0),
+            currency: None,
         };
         engine
             .process_transaction(deposit_tx)
@@ -167,7 +1224,8 @@ mod tests {
 
         let dispute_tx = TransactionType::Dispute {
             client: client_id,
-            tx: 4,
+            tx: TxId::from(4),
+            amount: None,
         };
         engine
             .process_transaction(dispute_tx)
@@ -175,16 +1233,1219 @@ mod tests {
 
         let chargeback_tx = TransactionType::Chargeback {
             client: client_id,
-            tx: 4,
+            tx: TxId::from(4),
+            amount: None,
         };
         engine
             .process_transaction(chargeback_tx)
             .expect("Could not chargeback transaction.");
 
         let client = engine.clients.get(&client_id).unwrap();
-        assert_eq!(client.available, Decimal::new(4, 0));
-        assert_eq!(client.total, Decimal::new(4, 0));
-        assert_eq!(client.held, Decimal::new(0, 0));
+        let balance = client.balance(&Currency::default());
+        assert_eq!(balance.available, Decimal::new(4, 0));
+        assert_eq!(balance.total, Decimal::new(4, 0));
+        assert_eq!(balance.held, Decimal::new(0, 0));
+        assert!(client.locked);
+
+        assert_eq!(
+            engine.dispute_history(client_id, TxId::from(4)),
+            Some(
+                [
+                    DisputeState::Settled,
+                    DisputeState::Disputed,
+                    DisputeState::ChargedBack
+                ]
+                .as_slice()
+            )
+        );
+        assert_eq!(engine.dispute_history(client_id, TxId::from(5)), None);
+    }
+
+    #[test]
+    fn test_redispute_policy() {
+        let mut engine = TransactionsEngine::new();
+        let client_id = ClientId(1);
+
+        engine
+            .process_transaction(TransactionType::Deposit {
+                client: client_id,
+                tx: TxId::from(1),
+                amount: Decimal::new(3, 0),
+                currency: None,
+            })
+            .expect("Could not process deposit.");
+
+        let dispute_tx = TransactionType::Dispute {
+            client: client_id,
+            tx: TxId::from(1),
+            amount: None,
+        };
+        engine
+            .process_transaction(dispute_tx.clone())
+            .expect("Could not dispute transaction.");
+        engine
+            .process_transaction(TransactionType::Resolve {
+                client: client_id,
+                tx: TxId::from(1),
+                amount: None,
+            })
+            .expect("Could not resolve transaction");
+
+        // by default a transaction can only be disputed once
+        assert!(engine.process_transaction(dispute_tx).is_err());
+
+        let client = engine.clients.get(&client_id).unwrap();
+        let balance = client.balance(&Currency::default());
+        assert_eq!(balance.available, Decimal::new(3, 0));
+        assert_eq!(balance.held, Decimal::new(0, 0));
+    }
+
+    #[test]
+    fn test_dispute_expiry() {
+        let config = EngineConfig {
+            dispute: DisputePolicy {
+                expiry: Some(DisputeExpiry {
+                    after: Window::Transactions(1),
+                    action: ExpiryAction::Chargeback,
+                }),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut engine = TransactionsEngine::with_config(config);
+        let client_id = ClientId(1);
+
+        for tx in [1, 2].map(TxId::from) {
+            engine
+                .process_transaction(TransactionType::Deposit {
+                    client: client_id,
+                    tx,
+                    amount: Decimal::new(3, 0),
+                    currency: None,
+                })
+                .expect("Could not process deposit.");
+        }
+
+        engine
+            .process_transaction(TransactionType::Dispute {
+                client: client_id,
+                tx: TxId::from(1),
+                amount: None,
+            })
+            .expect("Could not dispute transaction.");
+        engine
+            .process_transaction(TransactionType::Deposit {
+                client: client_id,
+                tx: TxId::from(3),
+                amount: Decimal::new(1, 0),
+                currency: None,
+            })
+            .expect("Could not process deposit.");
+
+        let client = engine.clients.get(&client_id).unwrap();
+        let balance = client.balance(&Currency::default());
+        assert_eq!(balance.held, Decimal::new(3, 0));
+        assert!(engine.events().is_empty());
+
+        // the dispute is charged back once more than 1 transaction has been processed since
+        engine
+            .process_transaction(TransactionType::Deposit {
+                client: ClientId(2),
+                tx: TxId::from(4),
+                amount: Decimal::new(1, 0),
+                currency: None,
+            })
+            .expect("Could not process deposit.");
+
+        let client = engine.clients.get(&client_id).unwrap();
+        let balance = client.balance(&Currency::default());
+        assert_eq!(balance.held, Decimal::new(0, 0));
+        assert_eq!(balance.total, Decimal::new(4, 0));
+        assert!(client.locked);
+
+        assert_eq!(
+            engine.events(),
+            [EngineEvent {
+                at: Instant {
+                    seq: 5,
+                    timestamp: None
+                },
+                kind: EventKind::DisputeExpired(ExpiryAction::Chargeback),
+                client: client_id,
+                tx: TxId::from(1),
+                amount: Decimal::new(3, 0),
+                currency: Currency::default(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_transfer() {
+        let mut engine = TransactionsEngine::new();
+
+        engine
+            .process_transaction(TransactionType::Deposit {
+                client: ClientId(1),
+                tx: TxId::from(1),
+                amount: Decimal::new(5, 0),
+                currency: None,
+            })
+            .expect("Could not process deposit.");
+
+        let transfer_tx = TransactionType::Transfer {
+            client: ClientId(1),
+            destination: ClientId(2),
+            tx: TxId::from(2),
+            amount: Decimal::new(3, 0),
+            currency: None,
+        };
+        engine
+            .process_transaction(transfer_tx.clone())
+            .expect("Could not process transfer.");
+
+        // check that processing the same transfer twice fails
+        assert!(engine.process_transaction(transfer_tx).is_err());
+
+        // insufficient funds on the source, none of the clients is updated
+        assert!(
+            engine
+                .process_transaction(TransactionType::Transfer {
+                    client: ClientId(1),
+                    destination: ClientId(2),
+                    tx: TxId::from(3),
+                    amount: Decimal::new(3, 0),
+                    currency: None,
+                })
+                .is_err()
+        );
+
+        let source = engine.clients.get(&ClientId(1)).unwrap();
+        let source_balance = source.balance(&Currency::default());
+        assert_eq!(source_balance.available, Decimal::new(2, 0));
+        assert_eq!(source_balance.total, Decimal::new(2, 0));
+        let destination = engine.clients.get(&ClientId(2)).unwrap();
+        let destination_balance = destination.balance(&Currency::default());
+        assert_eq!(destination_balance.available, Decimal::new(3, 0));
+        assert_eq!(destination_balance.total, Decimal::new(3, 0));
+        assert!(
+            !destination
+                .processed_transactions
+                .contains_key(&TxId::from(3))
+        );
+
+        // transfers are disputed by the client that received the funds
+        let dispute_tx = |client| TransactionType::Dispute {
+            client,
+            tx: TxId::from(2),
+            amount: None,
+        };
+        assert!(engine.process_transaction(dispute_tx(ClientId(1))).is_err());
+        engine
+            .process_transaction(dispute_tx(ClientId(2)))
+            .expect("Could not dispute transfer.");
+
+        let destination = engine.clients.get(&ClientId(2)).unwrap();
+        let destination_balance = destination.balance(&Currency::default());
+        assert_eq!(destination_balance.available, Decimal::new(0, 0));
+        assert_eq!(destination_balance.held, Decimal::new(3, 0));
+
+        // the chargeback returns the funds to the source
+        engine
+            .process_transaction(TransactionType::Chargeback {
+                client: ClientId(2),
+                tx: TxId::from(2),
+                amount: None,
+            })
+            .expect("Could not chargeback transfer.");
+
+        let source = engine.clients.get(&ClientId(1)).unwrap();
+        let source_balance = source.balance(&Currency::default());
+        assert_eq!(source_balance.available, Decimal::new(5, 0));
+        assert_eq!(source_balance.total, Decimal::new(5, 0));
+        assert!(!source.locked);
+        let destination = engine.clients.get(&ClientId(2)).unwrap();
+        let destination_balance = destination.balance(&Currency::default());
+        assert_eq!(destination_balance.held, Decimal::new(0, 0));
+        assert_eq!(destination_balance.total, Decimal::new(0, 0));
+        assert!(destination.locked);
+
+        // transfers to a locked client are rejected
+        assert!(
+            engine
+                .process_transaction(TransactionType::Transfer {
+                    client: ClientId(1),
+                    destination: ClientId(2),
+                    tx: TxId::from(4),
+                    amount: Decimal::new(1, 0),
+                    currency: None,
+                })
+                .is_err()
+        );
+        let source = engine.clients.get(&ClientId(1)).unwrap();
+        let source_balance = source.balance(&Currency::default());
+        assert_eq!(source_balance.available, Decimal::new(5, 0));
+
+        // a rejected transfer doesn't open the account of the destination
+        assert_eq!(
+            engine.process_transaction(TransactionType::Transfer {
+                client: ClientId(1),
+                destination: ClientId(9),
+                tx: TxId::from(5),
+                amount: Decimal::new(50, 0),
+                currency: None,
+            }),
+            Err(EngineError::InsufficientFunds)
+        );
+        assert!(engine.client(ClientId(9)).is_none());
+    }
+
+    #[test]
+    fn test_fees() {
+        let config = EngineConfig {
+            fees: FeeSchedule {
+                withdrawal: Some(FeeRule {
+                    fee: Fee::Percentage {
+                        percent: Decimal::new(1, 0),
+                    },
+                    min: Some(Decimal::new(1, 1)),
+                    max: None,
+                }),
+                chargeback: Some(FeeRule {
+                    fee: Fee::Flat {
+                        amount: Decimal::new(15, 0),
+                    },
+                    min: None,
+                    max: None,
+                }),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut engine = TransactionsEngine::with_config(config);
+        let client_id = ClientId(1);
+
+        for (tx, amount) in [
+            (TxId::from(1), Decimal::new(10, 0)),
+            (TxId::from(2), Decimal::new(20, 0)),
+        ] {
+            engine
+                .process_transaction(TransactionType::Deposit {
+                    client: client_id,
+                    tx,
+                    amount,
+                    currency: None,
+                })
+                .expect("Could not process deposit.");
+        }
+
+        engine
+            .process_transaction(TransactionType::Withdrawal {
+                client: client_id,
+                tx: TxId::from(3),
+                amount: Decimal::new(5, 0),
+                currency: None,
+            })
+            .expect("Could not process withdrawal.");
+
+        // the minimum fee is charged, the available funds don't cover the withdrawal and its fee
+        assert!(
+            engine
+                .process_transaction(TransactionType::Withdrawal {
+                    client: client_id,
+                    tx: TxId::from(4),
+                    amount: Decimal::new(2485, 2),
+                    currency: None,
+                })
+                .is_err()
+        );
+
+        let client = engine.clients.get(&client_id).unwrap();
+        let balance = client.balance(&Currency::default());
+        assert_eq!(balance.available, Decimal::new(249, 1));
+        assert_eq!(balance.fees, Decimal::new(1, 1));
+
+        for transaction in [
+            TransactionType::Dispute {
+                client: client_id,
+                tx: TxId::from(2),
+                amount: None,
+            },
+            TransactionType::Chargeback {
+                client: client_id,
+                tx: TxId::from(2),
+                amount: None,
+            },
+        ] {
+            engine
+                .process_transaction(transaction)
+                .expect("Could not process transaction.");
+        }
+
+        // the chargeback fee is charged even if the funds don't cover it
+        let client = engine.clients.get(&client_id).unwrap();
+        let balance = client.balance(&Currency::default());
+        assert_eq!(balance.available, Decimal::new(-101, 1));
+        assert_eq!(balance.total, Decimal::new(-101, 1));
+        assert_eq!(balance.fees, Decimal::new(151, 1));
+
+        assert_eq!(
+            engine
+                .ledger()
+                .balance(Account::House, &Currency::default()),
+            Decimal::new(151, 1)
+        );
+        assert_eq!(
+            engine
+                .ledger()
+                .balance(Account::Client(client_id), &Currency::default()),
+            Decimal::new(-151, 1)
+        );
+        assert_eq!(engine.ledger().entries().len(), 4);
+    }
+
+    #[test]
+    fn test_multi_currency() {
+        let eur = Currency::from("EUR");
+        let jpy = Currency::from("jpy");
+        let config = EngineConfig {
+            default_currency: eur.clone(),
+            currencies: HashMap::from([(jpy.clone(), CurrencyConfig { precision: 0 })]),
+            ..Default::default()
+        };
+        let mut engine = TransactionsEngine::with_config(config);
+        let client_id = ClientId(1);
+
+        for (tx, amount, currency) in [
+            (TxId::from(1), Decimal::new(10, 0), None),
+            (TxId::from(2), Decimal::new(500, 0), Some(jpy.clone())),
+        ] {
+            engine
+                .process_transaction(TransactionType::Deposit {
+                    client: client_id,
+                    tx,
+                    amount,
+                    currency,
+                })
+                .expect("Could not process deposit.");
+        }
+
+        // JPY amounts have no decimals
+        assert!(
+            engine
+                .process_transaction(TransactionType::Deposit {
+                    client: client_id,
+                    tx: TxId::from(3),
+                    amount: Decimal::new(15, 1),
+                    currency: Some(jpy.clone()),
+                })
+                .is_err()
+        );
+
+        // the EUR funds can't be used for a JPY withdrawal
+        assert!(
+            engine
+                .process_transaction(TransactionType::Withdrawal {
+                    client: client_id,
+                    tx: TxId::from(4),
+                    amount: Decimal::new(501, 0),
+                    currency: Some(jpy.clone()),
+                })
+                .is_err()
+        );
+
+        // the dispute holds the funds in the currency of the deposit
+        engine
+            .process_transaction(TransactionType::Dispute {
+                client: client_id,
+                tx: TxId::from(2),
+                amount: Some(Decimal::new(200, 0)),
+            })
+            .expect("Could not dispute transaction.");
+
+        let client = engine.clients.get(&client_id).unwrap();
+        let eur_balance = client.balance(&eur);
+        assert_eq!(eur_balance.available, Decimal::new(10, 0));
+        assert_eq!(eur_balance.held, Decimal::new(0, 0));
+        let jpy_balance = client.balance(&jpy);
+        assert_eq!(jpy_balance.available, Decimal::new(300, 0));
+        assert_eq!(jpy_balance.held, Decimal::new(200, 0));
+        assert_eq!(jpy_balance.total, Decimal::new(500, 0));
+    }
+
+    #[test]
+    fn test_conversion() {
+        let eur = Currency::from("EUR");
+        let usd = Currency::from("USD");
+        let config = EngineConfig {
+            default_currency: eur.clone(),
+            currencies: HashMap::from([(usd.clone(), CurrencyConfig { precision: 2 })]),
+            conversion: ConversionConfig {
+                spread_percent: Decimal::ONE,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut rates = RateTable::default();
+        rates.insert(eur.clone(), usd.clone(), Decimal::new(12345, 4), None);
+        rates.insert(usd.clone(), eur.clone(), Decimal::new(8, 1), None);
+        let mut engine = TransactionsEngine::with_config(config).with_rates(rates);
+        let client_id = ClientId(1);
+
+        engine
+            .process_transaction(TransactionType::Deposit {
+                client: client_id,
+                tx: TxId::from(1),
+                amount: Decimal::new(100, 0),
+                currency: None,
+            })
+            .expect("Could not process deposit.");
+        engine
+            .process_transaction(TransactionType::Convert {
+                client: client_id,
+                tx: TxId::from(2),
+                amount: Decimal::new(1001, 2),
+                currency: None,
+                to_currency: usd.clone(),
+            })
+            .expect("Could not process conversion.");
+
+        // not enough funds, and no rate to JPY
+        for (tx, amount, to_currency) in [
+            (TxId::from(3), Decimal::new(200, 0), usd.clone()),
+            (TxId::from(4), Decimal::new(1, 0), Currency::from("JPY")),
+        ] {
+            assert!(
+                engine
+                    .process_transaction(TransactionType::Convert {
+                        client: client_id,
+                        tx,
+                        amount,
+                        currency: None,
+                        to_currency,
+                    })
+                    .is_err()
+            );
+        }
+
+        // 10.01 EUR are 12.36 USD, minus a 1% spread
+        let client = engine.clients.get(&client_id).unwrap();
+        assert_eq!(client.balance(&eur).total, Decimal::new(8999, 2));
+        assert_eq!(client.balance(&usd).available, Decimal::new(1223, 2));
+        assert_eq!(
+            engine.ledger().balance(Account::House, &usd),
+            Decimal::new(13, 2)
+        );
+
+        // 89.99 EUR + 12.23 USD * 0.8
+        let mut valuation = Vec::new();
+        engine
+            .write_valuation(&mut valuation, &eur)
+            .expect("Could not write valuation.");
+        assert_eq!(
+            String::from_utf8(valuation).unwrap(),
+            "client,currency,total\n1,EUR,99.774\n"
+        );
+    }
+
+    #[test]
+    fn test_authorization() {
+        let config = EngineConfig {
+            authorization: AuthorizationPolicy {
+                expiry: Some(Window::Transactions(5)),
+            },
+            ..Default::default()
+        };
+        let mut engine = TransactionsEngine::with_config(config);
+        let client_id = ClientId(1);
+
+        for transaction in [
+            TransactionType::Deposit {
+                client: client_id,
+                tx: TxId::from(1),
+                amount: Decimal::new(10, 0),
+                currency: None,
+            },
+            TransactionType::Authorize {
+                client: client_id,
+                tx: TxId::from(2),
+                amount: Decimal::new(6, 0),
+                currency: None,
+            },
+            TransactionType::Capture {
+                client: client_id,
+                tx: TxId::from(2),
+                amount: Some(Decimal::new(2, 0)),
+            },
+        ] {
+            engine
+                .process_transaction(transaction)
+                .expect("Could not process transaction.");
+        }
+
+        // the reserved funds can't be withdrawn, and the authorization id can't be reused
+        for transaction in [
+            TransactionType::Withdrawal {
+                client: client_id,
+                tx: TxId::from(3),
+                amount: Decimal::new(5, 0),
+                currency: None,
+            },
+            TransactionType::Authorize {
+                client: client_id,
+                tx: TxId::from(2),
+                amount: Decimal::new(1, 0),
+                currency: None,
+            },
+            TransactionType::Capture {
+                client: client_id,
+                tx: TxId::from(1),
+                amount: None,
+            },
+        ] {
+            assert!(engine.process_transaction(transaction).is_err());
+        }
+
+        let balance = engine.clients[&client_id].balance(&Currency::default());
+        assert_eq!(balance.available, Decimal::new(4, 0));
+        assert_eq!(balance.reserved, Decimal::new(4, 0));
+        assert_eq!(balance.total, Decimal::new(8, 0));
+
+        // the authorization is not captured in time
+        engine
+            .process_transaction(TransactionType::Void {
+                client: client_id,
+                tx: TxId::from(4),
+            })
+            .expect_err("Voided an unknown authorization.");
+        engine
+            .process_transaction(TransactionType::Deposit {
+                client: client_id,
+                tx: TxId::from(5),
+                amount: Decimal::new(1, 0),
+                currency: None,
+            })
+            .expect("Could not process deposit.");
+
+        let balance = engine.clients[&client_id].balance(&Currency::default());
+        assert_eq!(balance.available, Decimal::new(9, 0));
+        assert_eq!(balance.reserved, Decimal::ZERO);
+        assert_eq!(balance.total, Decimal::new(9, 0));
+        assert_eq!(engine.events()[0].kind, EventKind::AuthorizationExpired);
+        assert_eq!(engine.events()[0].amount, Decimal::new(4, 0));
+
+        assert!(
+            engine
+                .process_transaction(TransactionType::Void {
+                    client: client_id,
+                    tx: TxId::from(2),
+                })
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_limits() {
+        let limits = Limits {
+            max_withdrawal: Some(Decimal::new(5, 0)),
+            max_balance: Some(Decimal::new(20, 0)),
+            ..Default::default()
+        };
+        let deposit = |tx| TransactionType::Deposit {
+            client: ClientId(1),
+            tx,
+            amount: Decimal::new(15, 0),
+            currency: None,
+        };
+        let withdrawal = TransactionType::Withdrawal {
+            client: ClientId(1),
+            tx: TxId::from(3),
+            amount: Decimal::new(6, 0),
+            currency: None,
+        };
+
+        // breaches are rejected by default
+        let mut engine = TransactionsEngine::with_config(EngineConfig {
+            limits: LimitsConfig {
+                default: limits.clone(),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        engine
+            .process_transaction(deposit(TxId::from(1)))
+            .expect("Could not process deposit.");
+        assert_eq!(
+            engine.process_transaction(deposit(TxId::from(2))),
+            Err(EngineError::LimitExceeded(Limit::MaxBalance))
+        );
+        assert_eq!(
+            engine.process_transaction(withdrawal.clone()),
+            Err(EngineError::LimitExceeded(Limit::MaxWithdrawal))
+        );
+
+        // transfers are limited like withdrawals on the source, and nothing moves
+        assert_eq!(
+            engine.process_transaction(TransactionType::Transfer {
+                client: ClientId(1),
+                destination: ClientId(2),
+                tx: TxId::from(4),
+                amount: Decimal::new(6, 0),
+                currency: None,
+            }),
+            Err(EngineError::LimitExceeded(Limit::MaxWithdrawal))
+        );
+        assert_eq!(
+            engine.clients[&ClientId(1)]
+                .balance(&Currency::default())
+                .total,
+            Decimal::new(15, 0)
+        );
+        assert!(engine.client(ClientId(2)).is_none());
+
+        // transfers add up with the withdrawals of the source and the deposits of the destination
+        let mut engine = TransactionsEngine::with_config(EngineConfig {
+            limits: LimitsConfig {
+                default: Limits {
+                    withdrawal_volume: Some(VolumeLimit {
+                        window: Window::Transactions(100),
+                        max: Decimal::new(5, 0),
+                    }),
+                    deposit_count: Some(CountLimit {
+                        window: Window::Transactions(100),
+                        max: 1,
+                    }),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        let transfer = |destination, tx| TransactionType::Transfer {
+            client: ClientId(1),
+            destination: ClientId(destination),
+            tx: TxId::from(tx),
+            amount: Decimal::new(4, 0),
+            currency: None,
+        };
+        engine
+            .process_transaction(TransactionType::Deposit {
+                client: ClientId(1),
+                tx: TxId::from(1),
+                amount: Decimal::new(100, 0),
+                currency: None,
+            })
+            .expect("Could not process deposit.");
+        engine
+            .process_transaction(transfer(2, 2))
+            .expect("Could not process transfer.");
+        assert_eq!(
+            engine.process_transaction(transfer(3, 3)),
+            Err(EngineError::LimitExceeded(Limit::WithdrawalVolume))
+        );
+        assert_eq!(
+            engine.process_transaction(TransactionType::Withdrawal {
+                client: ClientId(1),
+                tx: TxId::from(4),
+                amount: Decimal::new(4, 0),
+                currency: None,
+            }),
+            Err(EngineError::LimitExceeded(Limit::WithdrawalVolume))
+        );
+        engine
+            .process_transaction(TransactionType::Deposit {
+                client: ClientId(3),
+                tx: TxId::from(5),
+                amount: Decimal::new(10, 0),
+                currency: None,
+            })
+            .expect("Could not process deposit.");
+        assert_eq!(
+            engine.process_transaction(TransactionType::Transfer {
+                client: ClientId(3),
+                destination: ClientId(2),
+                tx: TxId::from(6),
+                amount: Decimal::new(4, 0),
+                currency: None,
+            }),
+            Err(EngineError::LimitExceeded(Limit::DepositCount))
+        );
+        assert_eq!(
+            engine.clients[&ClientId(1)]
+                .balance(&Currency::default())
+                .total,
+            Decimal::new(96, 0)
+        );
+
+        // windows in transactions count the transactions of the client only
+        let mut engine = TransactionsEngine::with_config(EngineConfig {
+            limits: LimitsConfig {
+                default: Limits {
+                    withdrawal_volume: Some(VolumeLimit {
+                        window: Window::Transactions(2),
+                        max: Decimal::new(5, 0),
+                    }),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        let deposit_of = |client, tx| TransactionType::Deposit {
+            client: ClientId(client),
+            tx: TxId::from(tx),
+            amount: Decimal::new(10, 0),
+            currency: None,
+        };
+        let withdrawal_of = |tx| TransactionType::Withdrawal {
+            client: ClientId(1),
+            tx: TxId::from(tx),
+            amount: Decimal::new(4, 0),
+            currency: None,
+        };
+        for transaction in [
+            deposit_of(1, 1),
+            withdrawal_of(2),
+            deposit_of(2, 3),
+            deposit_of(2, 4),
+        ] {
+            engine
+                .process_transaction(transaction)
+                .expect("Could not process transaction.");
+        }
+        assert_eq!(
+            engine.process_transaction(withdrawal_of(5)),
+            Err(EngineError::LimitExceeded(Limit::WithdrawalVolume))
+        );
+        for transaction in [deposit_of(1, 6), deposit_of(1, 7)] {
+            engine
+                .process_transaction(transaction)
+                .expect("Could not process deposit.");
+        }
+        engine
+            .process_transaction(withdrawal_of(8))
+            .expect("Could not process withdrawal.");
+
+        // in soft mode they are processed and flagged
+        let mut engine = TransactionsEngine::with_config(EngineConfig {
+            limits: LimitsConfig {
+                mode: LimitMode::Soft,
+                default: limits,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        for transaction in [deposit(TxId::from(1)), deposit(TxId::from(2)), withdrawal] {
+            engine
+                .process_transaction(transaction)
+                .expect("Could not process transaction.");
+        }
+
+        let balance = engine.clients[&ClientId(1)].balance(&Currency::default());
+        assert_eq!(balance.total, Decimal::new(24, 0));
+        assert_eq!(
+            engine
+                .events()
+                .iter()
+                .map(|event| (event.kind, event.tx))
+                .collect::<Vec<_>>(),
+            [
+                (EventKind::LimitExceeded(Limit::MaxBalance), TxId::from(2)),
+                (
+                    EventKind::LimitExceeded(Limit::MaxWithdrawal),
+                    TxId::from(3)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_risk_rules() {
+        let config = EngineConfig {
+            risk_rules: vec![RuleConfig::DisputeRate(DisputeRate {
+                max_percent: Decimal::new(50, 0),
+                min_deposits: 2,
+            })],
+            ..Default::default()
+        };
+        let mut engine = TransactionsEngine::with_config(config);
+        let client_id = ClientId(1);
+
+        for tx in (1..=3).map(TxId::from) {
+            engine
+                .process_transaction(TransactionType::Deposit {
+                    client: client_id,
+                    tx,
+                    amount: Decimal::new(10, 0),
+                    currency: None,
+                })
+                .expect("Could not process deposit.");
+        }
+
+        // the second dispute takes the dispute rate above 50%
+        for tx in (1..=2).map(TxId::from) {
+            engine
+                .process_transaction(TransactionType::Dispute {
+                    client: client_id,
+                    tx,
+                    amount: None,
+                })
+                .expect("Could not dispute transaction.");
+        }
+
+        let client = engine.clients.get(&client_id).unwrap();
+        assert_eq!(
+            client.balance(&Currency::default()).held,
+            Decimal::new(20, 0)
+        );
         assert!(client.locked);
+
+        assert_eq!(engine.alerts().len(), 1);
+        assert_eq!(engine.alerts()[0].rule, "dispute_rate");
+        assert_eq!(engine.alerts()[0].tx, TxId::from(2));
+        assert!(engine.alerts()[0].locked);
+    }
+
+    #[test]
+    fn test_overdraft() {
+        let config = EngineConfig {
+            limits: LimitsConfig {
+                tiers: HashMap::from([(
+                    "credit".to_string(),
+                    Limits {
+                        credit_limit: Some(Decimal::new(50, 0)),
+                        ..Default::default()
+                    },
+                )]),
+                client_tiers: HashMap::from([(ClientId(1), "credit".to_string())]),
+                ..Default::default()
+            },
+            fees: FeeSchedule {
+                overdraft: Some(FeeRule {
+                    fee: Fee::Percentage {
+                        percent: Decimal::new(10, 0),
+                    },
+                    min: None,
+                    max: None,
+                }),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut engine = TransactionsEngine::with_config(config);
+        let client_id = ClientId(1);
+        let withdrawal = |tx, amount| TransactionType::Withdrawal {
+            client: client_id,
+            tx,
+            amount,
+            currency: None,
+        };
+
+        engine
+            .process_transaction(TransactionType::Deposit {
+                client: client_id,
+                tx: TxId::from(1),
+                amount: Decimal::new(20, 0),
+                currency: None,
+            })
+            .expect("Could not process deposit.");
+
+        // 20 are drawn on the credit line, with a 10% fee
+        engine
+            .process_transaction(withdrawal(TxId::from(2), Decimal::new(40, 0)))
+            .expect("Could not process withdrawal.");
+        assert!(
+            engine
+                .process_transaction(withdrawal(TxId::from(3), Decimal::new(30, 0)))
+                .is_err()
+        );
+
+        let balance = engine.clients[&client_id].balance(&Currency::default());
+        assert_eq!(balance.available, Decimal::new(-22, 0));
+        assert_eq!(
+            balance.credit_used(Decimal::new(50, 0)),
+            Decimal::new(22, 0)
+        );
+        assert_eq!(
+            engine
+                .ledger()
+                .balance(Account::House, &Currency::default()),
+            Decimal::new(2, 0)
+        );
+
+        // the disputed deposit has been spent, holding it uses more of the credit line
+        engine
+            .process_transaction(TransactionType::Dispute {
+                client: client_id,
+                tx: TxId::from(1),
+                amount: None,
+            })
+            .expect("Could not dispute transaction.");
+        assert!(
+            engine
+                .process_transaction(withdrawal(TxId::from(4), Decimal::new(8, 0)))
+                .is_err()
+        );
+
+        let balance = engine.clients[&client_id].balance(&Currency::default());
+        assert_eq!(
+            balance.credit_used(Decimal::new(50, 0)),
+            Decimal::new(42, 0)
+        );
+        assert_eq!(balance.held, Decimal::new(20, 0));
+
+        // without a credit line, the spent funds held by a dispute are a shortfall, not credit
+        let mut engine = TransactionsEngine::new();
+        for transaction in [
+            TransactionType::Deposit {
+                client: client_id,
+                tx: TxId::from(1),
+                amount: Decimal::new(10, 0),
+                currency: None,
+            },
+            withdrawal(TxId::from(2), Decimal::new(8, 0)),
+            TransactionType::Dispute {
+                client: client_id,
+                tx: TxId::from(1),
+                amount: None,
+            },
+        ] {
+            engine
+                .process_transaction(transaction)
+                .expect("Could not process transaction.");
+        }
+
+        let client = &engine.clients[&client_id];
+        let balance = client.balance(&Currency::default());
+        assert_eq!(balance.available, Decimal::new(-8, 0));
+        assert_eq!(balance.credit_used(client.credit_limit), Decimal::ZERO);
+        assert_eq!(balance.shortfall(client.credit_limit), Decimal::new(8, 0));
+
+        // accounts at zero use no credit, without a sign
+        let mut engine = TransactionsEngine::new();
+        for transaction in [
+            TransactionType::Deposit {
+                client: client_id,
+                tx: TxId::from(1),
+                amount: Decimal::new(10, 1),
+                currency: None,
+            },
+            withdrawal(TxId::from(2), Decimal::new(10, 1)),
+            TransactionType::Withdrawal {
+                client: ClientId(7),
+                tx: TxId::from(3),
+                amount: Decimal::new(50, 1),
+                currency: None,
+            },
+        ] {
+            let _ = engine.process_transaction(transaction);
+        }
+
+        let mut output = Vec::new();
+        engine
+            .write_state(&mut output, engine.clients().into_iter())
+            .expect("Could not write state.");
+        let output = String::from_utf8(output).expect("Invalid output.");
+        let mut lines: Vec<_> = output.lines().skip(1).collect();
+        lines.sort();
+        assert_eq!(
+            lines,
+            ["1,,0.0,0,0,0.0,false,0,0,0,0", "7,,0,0,0,0,false,0,0,0,0"]
+        );
+    }
+
+    #[test]
+    fn test_interest() {
+        let config = EngineConfig {
+            interest: Some(InterestConfig {
+                tiers: vec![InterestTier {
+                    up_to: None,
+                    annual_percent: Decimal::new(365, 1),
+                }],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let mut engine = TransactionsEngine::with_config(config);
+        let client_id = ClientId(1);
+        let deposit = |tx, amount| TransactionType::Deposit {
+            client: client_id,
+            tx,
+            amount,
+            currency: None,
+        };
+
+        engine
+            .process_transaction_at(deposit(TxId::from(1), Decimal::new(5, 2)), Some(0))
+            .expect("Could not process deposit.");
+
+        // 0.1% of 0.05 a day, the interest is posted once it reaches 4 decimals
+        engine
+            .process_transaction(TransactionType::Accrue)
+            .expect("Could not accrue interest.");
+        let balance = engine.clients[&client_id].balance(&Currency::default());
+        assert_eq!(balance.available, Decimal::new(5, 2));
+        assert_eq!(balance.accrued_interest, Decimal::new(5, 5));
+
+        // the next day accrues interest again
+        engine
+            .process_transaction_at(deposit(TxId::from(2), Decimal::new(1, 0)), Some(86_400))
+            .expect("Could not process deposit.");
+        let balance = engine.clients[&client_id].balance(&Currency::default());
+        assert_eq!(balance.available, Decimal::new(10501, 4));
+        assert_eq!(balance.accrued_interest, Decimal::ZERO);
+
+        let entries = engine.ledger().entries();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].kind, EntryKind::Interest);
+        assert_eq!(entries[0].amount, Decimal::new(1, 4));
+        assert_eq!(
+            engine
+                .ledger()
+                .balance(Account::House, &Currency::default()),
+            Decimal::new(-1, 4)
+        );
+    }
+
+    #[test]
+    fn test_registry() {
+        let eur = Currency::from("EUR");
+        let mut registry = Registry::default();
+        registry.insert(RegisteredAccount {
+            client: ClientId(1),
+            currency: Some(eur.clone()),
+            credit_limit: Some(Decimal::new(5, 0)),
+            ..Default::default()
+        });
+        registry.insert(RegisteredAccount {
+            client: ClientId(2),
+            status: Some(AccountStatus::Locked),
+            ..Default::default()
+        });
+        let config = EngineConfig {
+            registry: RegistryConfig {
+                reject_unregistered: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut engine = TransactionsEngine::with_config(config).with_registry(registry);
+        let withdrawal = |client| TransactionType::Withdrawal {
+            client,
+            tx: TxId::from(1),
+            amount: Decimal::new(3, 0),
+            currency: None,
+        };
+
+        // the registered client uses its own currency and credit line
+        engine
+            .process_transaction(withdrawal(ClientId(1)))
+            .expect("Could not process withdrawal.");
+        assert_eq!(
+            engine.clients[&ClientId(1)].balance(&eur).available,
+            Decimal::new(-3, 0)
+        );
+
+        assert!(engine.process_transaction(withdrawal(ClientId(2))).is_err());
+        assert_eq!(
+            engine.process_transaction(withdrawal(ClientId(3))),
+            Err(EngineError::ClientNotRegistered)
+        );
+        assert!(!engine.clients.contains_key(&ClientId(3)));
+
+        // disputes never open accounts
+        let mut engine = TransactionsEngine::new();
+        assert!(
+            engine
+                .process_transaction(TransactionType::Dispute {
+                    client: ClientId(4),
+                    tx: TxId::from(1),
+                    amount: None,
+                })
+                .is_err()
+        );
+        assert!(engine.clients.is_empty());
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let config = EngineConfig {
+            dispute: DisputePolicy {
+                expiry: Some(DisputeExpiry {
+                    after: Window::Transactions(2),
+                    action: ExpiryAction::Resolve,
+                }),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut engine = TransactionsEngine::with_config(config.clone());
+        let client_id = ClientId(1);
+        for transaction in [
+            TransactionType::Deposit {
+                client: client_id,
+                tx: TxId::from(1),
+                amount: Decimal::new(10, 0),
+                currency: None,
+            },
+            TransactionType::Dispute {
+                client: client_id,
+                tx: TxId::from(1),
+                amount: None,
+            },
+        ] {
+            engine
+                .process_transaction(transaction)
+                .expect("Could not process transaction.");
+        }
+
+        let mut saved = Vec::new();
+        engine
+            .snapshot()
+            .write(&mut saved)
+            .expect("Could not write snapshot.");
+        let snapshot = Snapshot::from_reader(saved.as_slice()).expect("Could not read snapshot.");
+        let mut restored = TransactionsEngine::with_config(config).with_snapshot(snapshot);
+
+        let client = &restored.clients[&client_id];
+        assert_eq!(
+            client.balance(&Currency::default()).held,
+            Decimal::new(10, 0)
+        );
+        assert_eq!(
+            client.dispute_state(TxId::from(1)),
+            Some(DisputeState::Disputed)
+        );
+
+        // the restored engine keeps track of the open dispute and of the processed transactions
+        for tx in [2, 3, 4].map(TxId::from) {
+            restored
+                .process_transaction(TransactionType::Deposit {
+                    client: client_id,
+                    tx,
+                    amount: Decimal::new(1, 0),
+                    currency: None,
+                })
+                .expect("Could not process deposit.");
+        }
+        assert_eq!(
+            restored.dispute_history(client_id, TxId::from(1)),
+            Some(
+                [
+                    DisputeState::Settled,
+                    DisputeState::Disputed,
+                    DisputeState::Resolved
+                ]
+                .as_slice()
+            )
+        );
+        assert_eq!(
+            restored.process_transaction(TransactionType::Deposit {
+                client: client_id,
+                tx: TxId::from(1),
+                amount: Decimal::new(1, 0),
+                currency: None,
+            }),
+            Err(EngineError::DuplicateTransaction)
+        );
     }
 }
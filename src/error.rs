@@ -0,0 +1,167 @@
+use std::fmt;
+
+use crate::{currency::Currency, input::InputError, limits::Limit};
+
+/// Reason a transaction has been rejected by the engine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EngineError {
+    AccountLocked,
+    ClientNotFound,
+    ClientNotRegistered,
+    DuplicateTransaction,
+    TransactionNotFound,
+    NegativeAmount,
+    NonPositiveAmount,
+    InvalidPrecision,
+    InsufficientFunds,
+    InsufficientFundsForFee,
+    /// The transaction is not disputed
+    NotDisputed,
+    AlreadyDisputed,
+    ChargedBack,
+    MaxDisputesReached,
+    DisputeWindowExpired,
+    /// A transfer is disputed by its source client
+    TransferDisputedBySource,
+    /// The amount of a resolve or chargeback is above the disputed amount
+    ExceedsDisputed,
+    /// The amount of a refund is above what can be refunded
+    ExceedsRefundable,
+    /// The amount of a capture is above the reserved amount
+    ExceedsReserved,
+    NotRefundable,
+    AuthorizationNotFound,
+    AuthorizationNotPending,
+    SelfTransfer,
+    SameCurrencyConversion,
+    NoConversionRate {
+        from: Currency,
+        to: Currency,
+    },
+    LimitExceeded(Limit),
+    /// Rejected by the given risk rule for the given reason
+    RejectedByRule {
+        rule: String,
+        reason: String,
+    },
+}
+
+impl EngineError {
+    /// Name of the error variant, used in the API responses and metrics
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::AccountLocked => "account_locked",
+            Self::ClientNotFound => "client_not_found",
+            Self::ClientNotRegistered => "client_not_registered",
+            Self::DuplicateTransaction => "duplicate_transaction",
+            Self::TransactionNotFound => "transaction_not_found",
+            Self::NegativeAmount => "negative_amount",
+            Self::NonPositiveAmount => "non_positive_amount",
+            Self::InvalidPrecision => "invalid_precision",
+            Self::InsufficientFunds => "insufficient_funds",
+            Self::InsufficientFundsForFee => "insufficient_funds_for_fee",
+            Self::NotDisputed => "not_disputed",
+            Self::AlreadyDisputed => "already_disputed",
+            Self::ChargedBack => "charged_back",
+            Self::MaxDisputesReached => "max_disputes_reached",
+            Self::DisputeWindowExpired => "dispute_window_expired",
+            Self::TransferDisputedBySource => "transfer_disputed_by_source",
+            Self::ExceedsDisputed => "exceeds_disputed",
+            Self::ExceedsRefundable => "exceeds_refundable",
+            Self::ExceedsReserved => "exceeds_reserved",
+            Self::NotRefundable => "not_refundable",
+            Self::AuthorizationNotFound => "authorization_not_found",
+            Self::AuthorizationNotPending => "authorization_not_pending",
+            Self::SelfTransfer => "self_transfer",
+            Self::SameCurrencyConversion => "same_currency_conversion",
+            Self::NoConversionRate { .. } => "no_conversion_rate",
+            Self::LimitExceeded(_) => "limit_exceeded",
+            Self::RejectedByRule { .. } => "rejected_by_rule",
+        }
+    }
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AccountLocked => write!(f, "Account is locked, cannot process transaction"),
+            Self::ClientNotFound => write!(f, "Client account not found"),
+            Self::ClientNotRegistered => write!(f, "Client is not registered"),
+            Self::DuplicateTransaction => write!(f, "Transaction already processed"),
+            Self::TransactionNotFound => {
+                write!(f, "Transaction id not found in processed transactions")
+            }
+            Self::NegativeAmount => write!(f, "Negative amount"),
+            Self::NonPositiveAmount => write!(f, "Amount must be positive"),
+            Self::InvalidPrecision => write!(f, "Invalid decimal precision"),
+            Self::InsufficientFunds => write!(f, "Insufficient funds"),
+            Self::InsufficientFundsForFee => write!(f, "Insufficient funds to cover the fee"),
+            Self::NotDisputed => write!(f, "Transaction id has not been disputed"),
+            Self::AlreadyDisputed => write!(f, "Transaction already disputed"),
+            Self::ChargedBack => write!(f, "Transaction has been charged back"),
+            Self::MaxDisputesReached => {
+                write!(f, "Transaction reached the maximum number of disputes")
+            }
+            Self::DisputeWindowExpired => write!(f, "Dispute window has expired"),
+            Self::TransferDisputedBySource => write!(
+                f,
+                "Transfers can only be disputed by the destination client"
+            ),
+            Self::ExceedsDisputed => write!(f, "Amount exceeds the disputed amount"),
+            Self::ExceedsRefundable => write!(f, "Amount exceeds the refundable amount"),
+            Self::ExceedsReserved => write!(f, "Amount exceeds the reserved amount"),
+            Self::NotRefundable => write!(f, "Only deposits and withdrawals can be refunded"),
+            Self::AuthorizationNotFound => write!(f, "Authorization not found"),
+            Self::AuthorizationNotPending => write!(f, "Authorization is not pending"),
+            Self::SelfTransfer => write!(f, "Cannot transfer funds to the same client"),
+            Self::SameCurrencyConversion => {
+                write!(f, "Cannot convert funds to the same currency")
+            }
+            Self::NoConversionRate { from, to } => {
+                write!(f, "No conversion rate from {from} to {to}")
+            }
+            Self::LimitExceeded(limit) => write!(f, "Limit exceeded: {limit}"),
+            Self::RejectedByRule { rule, reason } => write!(f, "Rejected by {rule}: {reason}"),
+        }
+    }
+}
+
+/// Reason a transaction could not be processed, from reading it to applying it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransactionError {
+    /// The transaction could not be read
+    Input(InputError),
+    /// The transaction has been rejected by the engine
+    Engine(EngineError),
+}
+
+impl TransactionError {
+    /// Name of the error variant
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Input(err) => err.kind(),
+            Self::Engine(err) => err.kind(),
+        }
+    }
+}
+
+impl fmt::Display for TransactionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Input(err) => write!(f, "{err}"),
+            Self::Engine(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl From<InputError> for TransactionError {
+    fn from(err: InputError) -> Self {
+        Self::Input(err)
+    }
+}
+
+impl From<EngineError> for TransactionError {
+    fn from(err: EngineError) -> Self {
+        Self::Engine(err)
+    }
+}
@@ -0,0 +1,82 @@
+use std::fmt;
+
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    clock::Instant,
+    currency::Currency,
+    dispute::ExpiryAction,
+    ids::{ClientId, TxId},
+    limits::Limit,
+};
+
+/// Action taken by the engine on its own, rather than requested by an input transaction.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EngineEvent {
+    /// When the event happened
+    pub at: Instant,
+    /// Type of the event
+    pub kind: EventKind,
+    /// Client id
+    pub client: ClientId,
+    /// Id of the transaction the event refers to
+    pub tx: TxId,
+    /// Amount of money
+    pub amount: Decimal,
+    /// Currency of the amount
+    pub currency: Currency,
+}
+
+/// Event type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EventKind {
+    /// Dispute left open for too long, closed according to the expiry policy
+    DisputeExpired(ExpiryAction),
+    /// Authorization not captured in time, the reserved funds have been released
+    AuthorizationExpired,
+    /// Transaction breaching a limit, processed because limits are not enforced
+    LimitExceeded(Limit),
+}
+
+impl fmt::Display for EventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DisputeExpired(ExpiryAction::Resolve) => write!(f, "dispute_expired_resolve"),
+            Self::DisputeExpired(ExpiryAction::Chargeback) => {
+                write!(f, "dispute_expired_chargeback")
+            }
+            Self::AuthorizationExpired => write!(f, "authorization_expired"),
+            Self::LimitExceeded(limit) => write!(f, "{limit}_exceeded"),
+        }
+    }
+}
+
+impl EngineEvent {
+    /// Header of the events output
+    pub const HEADER: [&str; 7] = [
+        "event",
+        "client",
+        "tx",
+        "amount",
+        "currency",
+        "seq",
+        "timestamp",
+    ];
+
+    /// Fields of the events output, in the order of the header
+    pub fn to_record(&self) -> [String; 7] {
+        [
+            self.kind.to_string(),
+            self.client.to_string(),
+            self.tx.to_string(),
+            self.amount.to_string(),
+            self.currency.to_string(),
+            self.at.seq.to_string(),
+            self.at
+                .timestamp
+                .map(|timestamp| timestamp.to_string())
+                .unwrap_or_default(),
+        ]
+    }
+}
@@ -0,0 +1,195 @@
+use std::fmt;
+
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{Deserialize, Serialize};
+
+/// Transaction types that can be charged a fee.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeeType {
+    Deposit,
+    Withdrawal,
+    Transfer,
+    Chargeback,
+    Overdraft,
+}
+
+impl fmt::Display for FeeType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Deposit => write!(f, "deposit"),
+            Self::Withdrawal => write!(f, "withdrawal"),
+            Self::Transfer => write!(f, "transfer"),
+            Self::Chargeback => write!(f, "chargeback"),
+            Self::Overdraft => write!(f, "overdraft"),
+        }
+    }
+}
+
+/// How a fee is computed from the amount of a transaction.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Fee {
+    /// Same fee for any amount
+    Flat { amount: Decimal },
+    /// Percentage of the amount
+    Percentage { percent: Decimal },
+    /// Fee of the first tier that covers the amount
+    Tiered { tiers: Vec<FeeTier> },
+}
+
+/// Fee applied to the amounts up to `up_to`, or to any amount if there is no upper bound.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeeTier {
+    pub up_to: Option<Decimal>,
+    pub fee: Fee,
+}
+
+impl Fee {
+    /// Fee for the given amount, before capping and rounding
+    fn compute(&self, amount: Decimal) -> Decimal {
+        match self {
+            Self::Flat { amount } => *amount,
+            Self::Percentage { percent } => amount * percent / Decimal::ONE_HUNDRED,
+            Self::Tiered { tiers } => tiers
+                .iter()
+                .find(|tier| tier.up_to.is_none_or(|up_to| amount <= up_to))
+                .map_or(Decimal::ZERO, |tier| tier.fee.compute(amount)),
+        }
+    }
+}
+
+/// Fee charged for a transaction type, optionally capped between `min` and `max`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeeRule {
+    #[serde(flatten)]
+    pub fee: Fee,
+    pub min: Option<Decimal>,
+    pub max: Option<Decimal>,
+}
+
+impl FeeRule {
+    /// Fee for the given amount, rounded half away from zero to the given decimal precision
+    pub fn fee(&self, amount: Decimal, precision: u32) -> Decimal {
+        let mut fee = self.fee.compute(amount);
+
+        if let Some(min) = self.min {
+            fee = fee.max(min);
+        }
+        if let Some(max) = self.max {
+            fee = fee.min(max);
+        }
+
+        fee.round_dp_with_strategy(precision, RoundingStrategy::MidpointAwayFromZero)
+    }
+}
+
+/// Fees charged for each transaction type. Transaction types without a rule are free.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FeeSchedule {
+    pub deposit: Option<FeeRule>,
+    pub withdrawal: Option<FeeRule>,
+    pub transfer: Option<FeeRule>,
+    pub chargeback: Option<FeeRule>,
+    /// Fee on the amount of a withdrawal drawn on the credit line
+    pub overdraft: Option<FeeRule>,
+}
+
+impl FeeSchedule {
+    /// Fee charged for a transaction of the given type and amount, rounded to the given decimal
+    /// precision
+    pub fn fee(&self, fee_type: FeeType, amount: Decimal, precision: u32) -> Decimal {
+        let rule = match fee_type {
+            FeeType::Deposit => &self.deposit,
+            FeeType::Withdrawal => &self.withdrawal,
+            FeeType::Transfer => &self.transfer,
+            FeeType::Chargeback => &self.chargeback,
+            FeeType::Overdraft => &self.overdraft,
+        };
+
+        rule.as_ref()
+            .map_or(Decimal::ZERO, |rule| rule.fee(amount, precision))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+
+    use crate::types::DECIMAL_PRECISION;
+
+    use super::{Fee, FeeRule, FeeSchedule, FeeTier, FeeType};
+
+    #[test]
+    fn test_fee_rules() {
+        let schedule = FeeSchedule {
+            withdrawal: Some(FeeRule {
+                fee: Fee::Percentage {
+                    percent: Decimal::new(15, 1),
+                },
+                min: Some(Decimal::new(1, 1)),
+                max: Some(Decimal::new(5, 0)),
+            }),
+            deposit: Some(FeeRule {
+                fee: Fee::Tiered {
+                    tiers: vec![
+                        FeeTier {
+                            up_to: Some(Decimal::new(100, 0)),
+                            fee: Fee::Flat {
+                                amount: Decimal::new(1, 0),
+                            },
+                        },
+                        FeeTier {
+                            up_to: None,
+                            fee: Fee::Percentage {
+                                percent: Decimal::new(1, 0),
+                            },
+                        },
+                    ],
+                },
+                min: None,
+                max: None,
+            }),
+            ..Default::default()
+        };
+
+        // 1.5% of 10.0001 is 0.1500015, rounded to 4 decimals
+        assert_eq!(
+            schedule.fee(
+                FeeType::Withdrawal,
+                Decimal::new(100001, 4),
+                DECIMAL_PRECISION
+            ),
+            Decimal::new(1500, 4)
+        );
+        // minimum and maximum fees
+        assert_eq!(
+            schedule.fee(FeeType::Withdrawal, Decimal::new(1, 0), DECIMAL_PRECISION),
+            Decimal::new(1, 1)
+        );
+        assert_eq!(
+            schedule.fee(
+                FeeType::Withdrawal,
+                Decimal::new(1000, 0),
+                DECIMAL_PRECISION
+            ),
+            Decimal::new(5, 0)
+        );
+
+        assert_eq!(
+            schedule.fee(FeeType::Deposit, Decimal::new(100, 0), DECIMAL_PRECISION),
+            Decimal::new(1, 0)
+        );
+        assert_eq!(
+            schedule.fee(FeeType::Deposit, Decimal::new(250, 0), DECIMAL_PRECISION),
+            Decimal::new(25, 1)
+        );
+
+        // no rule for transfers
+        assert_eq!(
+            schedule.fee(FeeType::Transfer, Decimal::new(250, 0), DECIMAL_PRECISION),
+            Decimal::ZERO
+        );
+    }
+}
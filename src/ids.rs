@@ -0,0 +1,181 @@
+use std::{fmt, num::IntErrorKind, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+/// Reason an id could not be parsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdError {
+    /// The id is missing
+    Empty,
+    /// The id is a number too large for the id type
+    Overflow,
+    /// The id is neither a number nor a UUID
+    Malformed,
+}
+
+impl fmt::Display for IdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "empty"),
+            Self::Overflow => write!(f, "out of range"),
+            Self::Malformed => write!(f, "malformed"),
+        }
+    }
+}
+
+impl std::error::Error for IdError {}
+
+impl From<&IntErrorKind> for IdError {
+    fn from(kind: &IntErrorKind) -> Self {
+        match kind {
+            IntErrorKind::Empty => Self::Empty,
+            IntErrorKind::PosOverflow | IntErrorKind::NegOverflow => Self::Overflow,
+            _ => Self::Malformed,
+        }
+    }
+}
+
+/// Client id.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct ClientId(pub u64);
+
+impl From<u64> for ClientId {
+    fn from(id: u64) -> Self {
+        Self(id)
+    }
+}
+
+impl FromStr for ClientId {
+    type Err = IdError;
+
+    fn from_str(id: &str) -> Result<Self, Self::Err> {
+        id.parse()
+            .map(Self)
+            .map_err(|err| IdError::from(err.kind()))
+    }
+}
+
+impl fmt::Display for ClientId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Transaction id, either a 64-bit number or a UUID.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum TxId {
+    Number(u64),
+    Uuid(u128),
+}
+
+impl Default for TxId {
+    fn default() -> Self {
+        Self::Number(0)
+    }
+}
+
+impl From<u64> for TxId {
+    fn from(id: u64) -> Self {
+        Self::Number(id)
+    }
+}
+
+impl FromStr for TxId {
+    type Err = IdError;
+
+    fn from_str(id: &str) -> Result<Self, Self::Err> {
+        if id.len() == 36 && id.contains('-') {
+            return parse_uuid(id).map(Self::Uuid);
+        }
+
+        id.parse()
+            .map(Self::Number)
+            .map_err(|err| IdError::from(err.kind()))
+    }
+}
+
+impl TryFrom<String> for TxId {
+    type Error = String;
+
+    fn try_from(id: String) -> Result<Self, Self::Error> {
+        id.parse()
+            .map_err(|err| format!("Invalid transaction id {id:?}: {err}"))
+    }
+}
+
+impl From<TxId> for String {
+    fn from(id: TxId) -> Self {
+        id.to_string()
+    }
+}
+
+impl fmt::Display for TxId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Number(id) => write!(f, "{id}"),
+            Self::Uuid(id) => {
+                let hex = format!("{id:032x}");
+                write!(
+                    f,
+                    "{}-{}-{}-{}-{}",
+                    &hex[..8],
+                    &hex[8..12],
+                    &hex[12..16],
+                    &hex[16..20],
+                    &hex[20..]
+                )
+            }
+        }
+    }
+}
+
+/// Parse a UUID in its hyphenated form, such as `67e55044-10b1-426f-9247-bb680e5fe0c8`
+fn parse_uuid(id: &str) -> Result<u128, IdError> {
+    let groups: Vec<_> = id.split('-').collect();
+    let lengths: Vec<_> = groups.iter().map(|group| group.len()).collect();
+    if lengths != [8, 4, 4, 4, 12] {
+        return Err(IdError::Malformed);
+    }
+    // `from_str_radix` would accept a sign
+    if !groups
+        .iter()
+        .all(|group| group.bytes().all(|byte| byte.is_ascii_hexdigit()))
+    {
+        return Err(IdError::Malformed);
+    }
+
+    u128::from_str_radix(&groups.concat(), 16).map_err(|_| IdError::Malformed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ClientId, IdError, TxId};
+
+    #[test]
+    fn test_ids() {
+        assert_eq!("70000".parse(), Ok(ClientId(70_000)));
+        assert_eq!(
+            "18446744073709551616".parse::<ClientId>(),
+            Err(IdError::Overflow)
+        );
+        assert_eq!("abc".parse::<ClientId>(), Err(IdError::Malformed));
+        assert_eq!("".parse::<ClientId>(), Err(IdError::Empty));
+
+        assert_eq!("18446744073709551615".parse(), Ok(TxId::Number(u64::MAX)));
+        let uuid = "67e55044-10b1-426f-9247-bb680e5fe0c8";
+        let tx: TxId = uuid.parse().expect("Could not parse UUID.");
+        assert_eq!(tx.to_string(), uuid);
+        assert_eq!(
+            "67e55044-10b1-426f-9247-bb680e5fe0cz".parse::<TxId>(),
+            Err(IdError::Malformed)
+        );
+        assert_eq!(
+            "+2345678-1234-1234-1234-123456789abc".parse::<TxId>(),
+            Err(IdError::Malformed)
+        );
+    }
+}
@@ -0,0 +1,291 @@
+use std::{fmt, io::Read};
+
+use csv::{Reader, ReaderBuilder, StringRecord, Trim};
+use serde_json::{Map, Value};
+
+use crate::{
+    ids::IdError,
+    types::{TransactionRecord, TransactionType},
+};
+
+/// Reason a line of the input could not be read as a transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InputError {
+    /// The line doesn't have the columns of a transaction record
+    Malformed(String),
+    /// A client or transaction id could not be parsed
+    InvalidId {
+        field: &'static str,
+        value: String,
+        error: IdError,
+    },
+    /// The amount is not a decimal number
+    InvalidAmount(String),
+    /// A field needed by the transaction type is missing
+    MissingField(&'static str),
+}
+
+impl InputError {
+    /// Name of the error variant
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Malformed(_) => "malformed",
+            Self::InvalidId { .. } => "invalid_id",
+            Self::InvalidAmount(_) => "invalid_amount",
+            Self::MissingField(_) => "missing_field",
+        }
+    }
+}
+
+impl fmt::Display for InputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Malformed(reason) => write!(f, "Malformed record: {reason}"),
+            Self::InvalidId {
+                field,
+                value,
+                error,
+            } => write!(f, "Invalid {field} id {value:?}: {error}"),
+            Self::InvalidAmount(reason) => write!(f, "Invalid amount: {reason}"),
+            Self::MissingField(field) => write!(f, "Missing {field}"),
+        }
+    }
+}
+
+/// Input error and the line it was found on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineError {
+    /// Line of the input, starting at 1 with the header
+    pub line: u64,
+    pub error: InputError,
+}
+
+impl fmt::Display for LineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Line {}: {}", self.line, self.error)
+    }
+}
+
+/// Transaction read from the input.
+#[derive(Debug, Clone)]
+pub struct InputTransaction {
+    /// Line of the input
+    pub line: u64,
+    pub transaction: TransactionType,
+    /// Unix timestamp in seconds, if the input provides one
+    pub timestamp: Option<u64>,
+}
+
+/// Reads the transactions of a CSV input, one per line after the header.
+pub struct TransactionReader<R> {
+    reader: Reader<R>,
+    headers: Option<StringRecord>,
+}
+
+impl<R: Read> TransactionReader<R> {
+    pub fn new(reader: R) -> Self {
+        let reader = ReaderBuilder::new()
+            .flexible(true)
+            .has_headers(true)
+            .trim(Trim::All)
+            .from_reader(reader);
+
+        Self {
+            reader,
+            headers: None,
+        }
+    }
+
+    fn headers(&mut self) -> Result<StringRecord, LineError> {
+        if let Some(headers) = &self.headers {
+            return Ok(headers.clone());
+        }
+
+        let headers = self.reader.headers().cloned().map_err(|err| LineError {
+            line: 1,
+            error: InputError::Malformed(err.to_string()),
+        })?;
+        self.headers = Some(headers.clone());
+
+        Ok(headers)
+    }
+}
+
+impl<R: Read> Iterator for TransactionReader<R> {
+    type Item = Result<InputTransaction, LineError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let headers = match self.headers() {
+            Ok(headers) => headers,
+            Err(err) => return Some(Err(err)),
+        };
+
+        let mut record = StringRecord::new();
+        let line = match self.reader.read_record(&mut record) {
+            Ok(false) => return None,
+            Ok(true) => record.position().map_or(0, |position| position.line()),
+            Err(err) => {
+                let line = err.position().map_or(0, |position| position.line());
+                return Some(Err(LineError {
+                    line,
+                    error: InputError::Malformed(err.to_string()),
+                }));
+            }
+        };
+
+        Some(
+            parse_record(&record, &headers)
+                .map_err(|error| LineError { line, error })
+                .map(|(transaction, timestamp)| InputTransaction {
+                    line,
+                    transaction,
+                    timestamp,
+                }),
+        )
+    }
+}
+
+/// Map a JSON object with the fields of a transaction record to a transaction and its timestamp.
+/// Ids and amounts can be given as numbers or strings.
+pub fn parse_json(value: &Value) -> Result<(TransactionType, Option<u64>), InputError> {
+    let Value::Object(fields) = value else {
+        return Err(InputError::Malformed("Expected an object".to_string()));
+    };
+
+    let fields: Map<_, _> = fields
+        .iter()
+        .map(|(name, value)| match (name.as_str(), value) {
+            ("client" | "tx" | "destination" | "amount", Value::Number(number)) => {
+                (name.clone(), Value::String(number.to_string()))
+            }
+            _ => (name.clone(), value.clone()),
+        })
+        .collect();
+    let record: TransactionRecord = serde_json::from_value(Value::Object(fields))
+        .map_err(|err| InputError::Malformed(err.to_string()))?;
+    let timestamp = record.timestamp;
+
+    Ok((TransactionType::from_transaction_record(record)?, timestamp))
+}
+
+/// Read a single CSV line, without header
+pub fn read_csv_line(line: &str) -> Result<StringRecord, InputError> {
+    let mut reader = ReaderBuilder::new()
+        .flexible(true)
+        .has_headers(false)
+        .trim(Trim::All)
+        .from_reader(line.as_bytes());
+
+    let mut record = StringRecord::new();
+    match reader.read_record(&mut record) {
+        Ok(true) => Ok(record),
+        Ok(false) => Err(InputError::Malformed("Empty line".to_string())),
+        Err(err) => Err(InputError::Malformed(err.to_string())),
+    }
+}
+
+/// Map a CSV record to a transaction and its timestamp
+pub fn parse_record(
+    record: &StringRecord,
+    headers: &StringRecord,
+) -> Result<(TransactionType, Option<u64>), InputError> {
+    let record: TransactionRecord = record
+        .deserialize(Some(headers))
+        .map_err(|err| InputError::Malformed(err.to_string()))?;
+    let timestamp = record.timestamp;
+
+    Ok((TransactionType::from_transaction_record(record)?, timestamp))
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+
+    use crate::{
+        ids::{ClientId, IdError, TxId},
+        types::TransactionType,
+    };
+
+    use super::{InputError, InputTransaction, LineError, TransactionReader, parse_json};
+
+    #[test]
+    fn test_reader() {
+        let input = "type, client, tx, amount\n\
+            deposit, 70000, 18446744073709551615, 1.0\n\
+            deposit, 18446744073709551616, 2, 1.0\n\
+            withdrawal, 1, abc, 1.0\n\
+            deposit, 1, 67e55044-10b1-426f-9247-bb680e5fe0c8, 1.0\n\
+            deposit, 1\n\
+            dispute, 1, 1, 0\n\
+            dispute, 1, 1,\n";
+        let results: Vec<_> = TransactionReader::new(input.as_bytes()).collect();
+        assert_eq!(results.len(), 7);
+
+        let transaction = results[0].as_ref().expect("Could not read line.");
+        assert_eq!(transaction.line, 2);
+        assert_eq!(
+            transaction.transaction.ids(),
+            Some((ClientId(70_000), TxId::Number(u64::MAX)))
+        );
+
+        assert_eq!(
+            results[1].as_ref().err(),
+            Some(&LineError {
+                line: 3,
+                error: InputError::InvalidId {
+                    field: "client",
+                    value: "18446744073709551616".to_string(),
+                    error: IdError::Overflow,
+                },
+            })
+        );
+        assert_eq!(
+            results[2].as_ref().map_err(|err| err.to_string()).err(),
+            Some("Line 4: Invalid tx id \"abc\": malformed".to_string())
+        );
+        assert!(results[3].is_ok());
+        assert_eq!(
+            results[4].as_ref().err(),
+            Some(&LineError {
+                line: 6,
+                error: InputError::MissingField("tx"),
+            })
+        );
+
+        // Only a missing amount refers to the whole transaction
+        let amount = |result: &Result<InputTransaction, LineError>| match result {
+            Ok(InputTransaction {
+                transaction: TransactionType::Dispute { amount, .. },
+                ..
+            }) => Some(*amount),
+            _ => None,
+        };
+        assert_eq!(amount(&results[5]), Some(Some(Decimal::ZERO)));
+        assert_eq!(amount(&results[6]), Some(None));
+    }
+
+    #[test]
+    fn test_parse_json() {
+        let (transaction, timestamp) = parse_json(&serde_json::json!({
+            "type": "deposit",
+            "client": 70000,
+            "tx": "67e55044-10b1-426f-9247-bb680e5fe0c8",
+            "amount": "1.5",
+            "timestamp": 1_700_000_000,
+        }))
+        .expect("Could not parse transaction.");
+        assert_eq!(
+            transaction.ids().map(|(client, _)| client),
+            Some(ClientId(70_000))
+        );
+        assert_eq!(timestamp, Some(1_700_000_000));
+
+        assert_eq!(
+            parse_json(&serde_json::json!({"type": "deposit", "client": -1, "tx": 1}))
+                .err()
+                .map(|err| err.kind()),
+            Some("invalid_id")
+        );
+        assert!(parse_json(&serde_json::json!([])).is_err());
+    }
+}
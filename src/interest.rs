@@ -0,0 +1,97 @@
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{Deserialize, Serialize};
+
+/// Decimal precision of the interest accrued and not posted yet
+pub const INTEREST_PRECISION: u32 = 12;
+
+/// Annual interest rate applied to the available funds up to `up_to`, or to any amount if there is
+/// no upper bound.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InterestTier {
+    pub up_to: Option<Decimal>,
+    pub annual_percent: Decimal,
+}
+
+/// Interest paid daily on the available funds.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InterestConfig {
+    /// Rate of the first tier that covers the available funds
+    pub tiers: Vec<InterestTier>,
+    /// Number of days the annual rates are divided into
+    pub days_per_year: u32,
+}
+
+impl Default for InterestConfig {
+    fn default() -> Self {
+        Self {
+            tiers: Vec::new(),
+            days_per_year: 365,
+        }
+    }
+}
+
+impl InterestConfig {
+    /// Interest earned in a day by the given available funds, rounded half to even to the
+    /// internal interest precision. Negative funds don't earn interest.
+    pub fn daily_interest(&self, available: Decimal) -> Decimal {
+        if available <= Decimal::ZERO || self.days_per_year == 0 {
+            return Decimal::ZERO;
+        }
+
+        let Some(tier) = self
+            .tiers
+            .iter()
+            .find(|tier| tier.up_to.is_none_or(|up_to| available <= up_to))
+        else {
+            return Decimal::ZERO;
+        };
+
+        (available * tier.annual_percent / Decimal::ONE_HUNDRED / Decimal::from(self.days_per_year))
+            .round_dp_with_strategy(INTEREST_PRECISION, RoundingStrategy::MidpointNearestEven)
+    }
+}
+
+/// Part of the accrued interest that can be posted at the given precision, rounded towards zero.
+/// The rest stays accrued until it adds up to a postable amount.
+pub fn postable(accrued: Decimal, precision: u32) -> Decimal {
+    accrued.round_dp_with_strategy(precision, RoundingStrategy::ToZero)
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+
+    use crate::types::DECIMAL_PRECISION;
+
+    use super::{InterestConfig, InterestTier, postable};
+
+    #[test]
+    fn test_daily_interest() {
+        let config = InterestConfig {
+            tiers: vec![
+                InterestTier {
+                    up_to: Some(Decimal::new(1000, 0)),
+                    annual_percent: Decimal::new(1, 0),
+                },
+                InterestTier {
+                    up_to: None,
+                    annual_percent: Decimal::new(2, 0),
+                },
+            ],
+            ..Default::default()
+        };
+
+        // 100 * 1% / 365
+        let interest = config.daily_interest(Decimal::new(100, 0));
+        assert_eq!(interest, Decimal::new(2739726027, 12));
+        assert_eq!(postable(interest, DECIMAL_PRECISION), Decimal::new(27, 4));
+
+        // 3650 * 2% / 365
+        assert_eq!(
+            config.daily_interest(Decimal::new(3650, 0)),
+            Decimal::new(2, 1)
+        );
+        assert_eq!(config.daily_interest(Decimal::new(-100, 0)), Decimal::ZERO);
+    }
+}
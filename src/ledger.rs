@@ -0,0 +1,146 @@
+use std::{collections::HashMap, fmt};
+
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    clock::Instant,
+    currency::Currency,
+    fees::FeeType,
+    ids::{ClientId, TxId},
+};
+
+/// Account a ledger line is posted to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Account {
+    /// Client account
+    Client(ClientId),
+    /// Account collecting the revenue of the engine, fees and conversion spreads, and paying the
+    /// interest
+    House,
+}
+
+impl fmt::Display for Account {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Client(client) => write!(f, "{client}"),
+            Self::House => write!(f, "house"),
+        }
+    }
+}
+
+/// Ledger line type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EntryKind {
+    /// Fee charged for a transaction
+    Fee(FeeType),
+    /// Spread kept from a currency conversion
+    ConversionSpread,
+    /// Interest paid to a client
+    Interest,
+}
+
+impl fmt::Display for EntryKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fee(fee_type) => write!(f, "{fee_type}_fee"),
+            Self::ConversionSpread => write!(f, "conversion_spread"),
+            Self::Interest => write!(f, "interest"),
+        }
+    }
+}
+
+/// Single ledger line, crediting or debiting (negative amount) an account.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    /// When the line has been posted
+    pub at: Instant,
+    /// Id of the transaction the line refers to, if any
+    pub tx: Option<TxId>,
+    /// Account the line is posted to
+    pub account: Account,
+    /// Type of the line
+    pub kind: EntryKind,
+    /// Amount of money
+    pub amount: Decimal,
+    /// Currency of the amount
+    pub currency: Currency,
+}
+
+impl LedgerEntry {
+    /// Header of the ledger output
+    pub const HEADER: [&str; 7] = [
+        "seq",
+        "timestamp",
+        "tx",
+        "account",
+        "kind",
+        "amount",
+        "currency",
+    ];
+
+    /// Fields of the ledger output, in the order of the header
+    pub fn to_record(&self) -> [String; 7] {
+        [
+            self.at.seq.to_string(),
+            self.at
+                .timestamp
+                .map(|timestamp| timestamp.to_string())
+                .unwrap_or_default(),
+            self.tx.map(|tx| tx.to_string()).unwrap_or_default(),
+            self.account.to_string(),
+            self.kind.to_string(),
+            self.amount.to_string(),
+            self.currency.to_string(),
+        ]
+    }
+}
+
+/// Ledger keeping the lines posted by the engine and the balance of each account and currency.
+/// Only the lines are serialized, the balances are computed again when they are read.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(from = "Vec<LedgerEntry>", into = "Vec<LedgerEntry>")]
+pub struct Ledger {
+    entries: Vec<LedgerEntry>,
+    balances: HashMap<(Account, Currency), Decimal>,
+}
+
+impl From<Vec<LedgerEntry>> for Ledger {
+    fn from(entries: Vec<LedgerEntry>) -> Self {
+        let mut ledger = Self::default();
+        for entry in entries {
+            ledger.post(entry);
+        }
+        ledger
+    }
+}
+
+impl From<Ledger> for Vec<LedgerEntry> {
+    fn from(ledger: Ledger) -> Self {
+        ledger.entries
+    }
+}
+
+impl Ledger {
+    /// Post a new line
+    pub fn post(&mut self, entry: LedgerEntry) {
+        *self
+            .balances
+            .entry((entry.account, entry.currency.clone()))
+            .or_default() += entry.amount;
+        self.entries.push(entry);
+    }
+
+    /// Posted lines, in order
+    pub fn entries(&self) -> &[LedgerEntry] {
+        &self.entries
+    }
+
+    /// Sum of the lines posted to the given account in the given currency
+    pub fn balance(&self, account: Account, currency: &Currency) -> Decimal {
+        self.balances
+            .get(&(account, currency.clone()))
+            .copied()
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,31 @@
+pub mod aging;
+pub mod authorization;
+pub mod client;
+pub mod clock;
+pub mod config;
+pub mod conversion;
+pub mod currency;
+pub mod diff;
+pub mod dispute;
+pub mod dry_run;
+pub mod engine;
+pub mod error;
+pub mod event;
+pub mod fees;
+pub mod ids;
+pub mod input;
+pub mod interest;
+pub mod ledger;
+pub mod limits;
+pub mod metrics;
+pub mod output;
+pub mod reconcile;
+pub mod registry;
+pub mod repl;
+pub mod risk;
+pub mod server;
+pub mod snapshot;
+pub mod socket;
+pub mod statement;
+pub mod stats;
+pub mod types;
@@ -0,0 +1,346 @@
+use std::{collections::HashMap, fmt};
+
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    clock::{Instant, Window},
+    currency::Currency,
+    ids::ClientId,
+    types::TransactionType,
+};
+
+/// Risk limit a transaction can breach.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Limit {
+    MaxWithdrawal,
+    WithdrawalVolume,
+    DepositCount,
+    MaxBalance,
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MaxWithdrawal => write!(f, "max_withdrawal"),
+            Self::WithdrawalVolume => write!(f, "withdrawal_volume"),
+            Self::DepositCount => write!(f, "deposit_count"),
+            Self::MaxBalance => write!(f, "max_balance"),
+        }
+    }
+}
+
+/// What happens when a transaction breaches a limit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LimitMode {
+    /// The transaction is rejected
+    #[default]
+    Hard,
+    /// The transaction is processed and flagged with an event
+    Soft,
+}
+
+/// Maximum amount moved in a window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VolumeLimit {
+    pub window: Window,
+    pub max: Decimal,
+}
+
+/// Maximum number of transactions in a window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CountLimit {
+    pub window: Window,
+    pub max: u32,
+}
+
+/// Risk limits of a client. Amounts are checked in the currency of the transaction, missing
+/// limits are not enforced.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Limits {
+    /// Maximum amount of a single withdrawal
+    pub max_withdrawal: Option<Decimal>,
+    /// Maximum amount withdrawn in a window, such as a day or the last N transactions
+    pub withdrawal_volume: Option<VolumeLimit>,
+    /// Maximum number of deposits in a window
+    pub deposit_count: Option<CountLimit>,
+    /// Maximum total funds
+    pub max_balance: Option<Decimal>,
+    /// Amount the available funds can go below zero, enforced whatever the limit mode
+    pub credit_limit: Option<Decimal>,
+}
+
+impl Limits {
+    /// Limits breached by a withdrawal of the given amount
+    pub fn check_withdrawal(
+        &self,
+        activity: &Activity,
+        amount: Decimal,
+        currency: &Currency,
+        now: Instant,
+    ) -> Vec<Limit> {
+        let mut breached = Vec::new();
+
+        if self.max_withdrawal.is_some_and(|max| amount > max) {
+            breached.push(Limit::MaxWithdrawal);
+        }
+
+        if let Some(limit) = self.withdrawal_volume {
+            let volume: Decimal = activity.withdrawals(limit.window, currency, now).sum();
+            if volume + amount > limit.max {
+                breached.push(Limit::WithdrawalVolume);
+            }
+        }
+
+        breached
+    }
+
+    /// Limits breached by a deposit of the given amount on top of the current total funds
+    pub fn check_deposit(
+        &self,
+        activity: &Activity,
+        total: Decimal,
+        amount: Decimal,
+        currency: &Currency,
+        now: Instant,
+    ) -> Vec<Limit> {
+        let mut breached = Vec::new();
+
+        if let Some(limit) = self.deposit_count {
+            let count = activity.deposits(limit.window, currency, now).count();
+            if count >= limit.max as usize {
+                breached.push(Limit::DepositCount);
+            }
+        }
+
+        breached.extend(self.check_balance(total, amount));
+
+        breached
+    }
+
+    /// Limit breached by receiving the given amount on top of the current total funds
+    pub fn check_balance(&self, total: Decimal, amount: Decimal) -> Option<Limit> {
+        self.max_balance
+            .filter(|max| total + amount > *max)
+            .map(|_| Limit::MaxBalance)
+    }
+}
+
+/// Risk limits of the clients: the limits of the client if it has its own, otherwise the limits
+/// of its tier, otherwise the default limits.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LimitsConfig {
+    pub mode: LimitMode,
+    pub default: Limits,
+    /// Limits of each tier
+    pub tiers: HashMap<String, Limits>,
+    /// Tier of each client
+    pub client_tiers: HashMap<ClientId, String>,
+    /// Limits of specific clients
+    pub clients: HashMap<ClientId, Limits>,
+}
+
+impl LimitsConfig {
+    /// Limits applied to the given client
+    pub fn limits(&self, client: ClientId) -> &Limits {
+        self.clients
+            .get(&client)
+            .or_else(|| {
+                self.client_tiers
+                    .get(&client)
+                    .and_then(|tier| self.tiers.get(tier))
+            })
+            .unwrap_or(&self.default)
+    }
+}
+
+/// Deposits, withdrawals and transfers of a client, used by the velocity controls. Windows in
+/// transactions count the transactions of the client, not the ones of the whole engine.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Activity {
+    /// Number of transactions recorded for the client
+    #[serde(default)]
+    transactions: u64,
+    /// Entries are recorded with the sequence number of the client's own transactions
+    deposits: Vec<(Instant, Currency, Decimal)>,
+    withdrawals: Vec<(Instant, Currency, Decimal)>,
+}
+
+impl Activity {
+    /// Record a processed transaction of the given client. Transfers are withdrawals for their
+    /// source and deposits for their destination, other transactions are ignored.
+    pub fn record(&mut self, client: ClientId, transaction: &TransactionType, at: Instant) {
+        self.transactions += 1;
+        let at = Instant {
+            seq: self.transactions,
+            ..at
+        };
+
+        let (entries, amount, currency) = match transaction {
+            TransactionType::Deposit {
+                amount, currency, ..
+            } => (&mut self.deposits, amount, currency),
+            TransactionType::Withdrawal {
+                amount, currency, ..
+            } => (&mut self.withdrawals, amount, currency),
+            TransactionType::Transfer {
+                client: source,
+                amount,
+                currency,
+                ..
+            } if *source == client => (&mut self.withdrawals, amount, currency),
+            TransactionType::Transfer {
+                amount, currency, ..
+            } => (&mut self.deposits, amount, currency),
+            _ => return,
+        };
+
+        entries.push((at, currency.clone().unwrap_or_default(), *amount));
+    }
+
+    /// Amounts deposited in the given currency within the window before `now`, latest first
+    pub fn deposits<'a>(
+        &'a self,
+        window: Window,
+        currency: &'a Currency,
+        now: Instant,
+    ) -> impl Iterator<Item = Decimal> + 'a {
+        self.recent(&self.deposits, window, currency, now)
+    }
+
+    /// Amounts withdrawn in the given currency within the window before `now`, latest first
+    pub fn withdrawals<'a>(
+        &'a self,
+        window: Window,
+        currency: &'a Currency,
+        now: Instant,
+    ) -> impl Iterator<Item = Decimal> + 'a {
+        self.recent(&self.withdrawals, window, currency, now)
+    }
+
+    /// Amounts in the given currency recorded within the window before `now`, the transaction
+    /// being processed coming after the last one recorded for the client
+    fn recent<'a>(
+        &self,
+        entries: &'a [(Instant, Currency, Decimal)],
+        window: Window,
+        currency: &'a Currency,
+        now: Instant,
+    ) -> impl Iterator<Item = Decimal> + 'a {
+        let now = Instant {
+            seq: self.transactions + 1,
+            ..now
+        };
+
+        entries
+            .iter()
+            .rev()
+            .take_while(move |(at, _, _)| !window.has_elapsed(*at, now))
+            .filter(move |(_, entry_currency, _)| entry_currency == currency)
+            .map(|(_, _, amount)| *amount)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use rust_decimal::Decimal;
+
+    use crate::{
+        clock::{Instant, Window},
+        currency::Currency,
+        ids::{ClientId, TxId},
+        types::TransactionType,
+    };
+
+    use super::{Activity, CountLimit, Limit, Limits, LimitsConfig, VolumeLimit};
+
+    #[test]
+    fn test_limits() {
+        let currency = Currency::default();
+        let limits = Limits {
+            max_withdrawal: Some(Decimal::new(50, 0)),
+            withdrawal_volume: Some(VolumeLimit {
+                window: Window::Transactions(2),
+                max: Decimal::new(80, 0),
+            }),
+            deposit_count: Some(CountLimit {
+                window: Window::Days(1),
+                max: 1,
+            }),
+            max_balance: Some(Decimal::new(100, 0)),
+            credit_limit: None,
+        };
+
+        // the withdrawals of the client are interleaved with the transactions of other clients
+        let at = |seq| Instant {
+            seq,
+            timestamp: Some(0),
+        };
+        let mut activity = Activity::default();
+        for (seq, amount) in [(1, Decimal::new(40, 0)), (5, Decimal::new(30, 0))] {
+            activity.record(
+                ClientId(1),
+                &TransactionType::Withdrawal {
+                    client: ClientId(1),
+                    tx: TxId::from(seq),
+                    amount,
+                    currency: None,
+                },
+                at(seq),
+            );
+        }
+
+        // the window counts the transactions of the client, not the ones of the engine
+        assert_eq!(
+            limits.check_withdrawal(&activity, Decimal::new(20, 0), &currency, at(9)),
+            [Limit::WithdrawalVolume]
+        );
+
+        // the first withdrawal is out of the window after another transaction of the client
+        activity.record(
+            ClientId(1),
+            &TransactionType::Deposit {
+                client: ClientId(1),
+                tx: TxId::from(10),
+                amount: Decimal::new(10, 0),
+                currency: None,
+            },
+            at(10),
+        );
+        assert!(
+            limits
+                .check_withdrawal(&activity, Decimal::new(20, 0), &currency, at(11))
+                .is_empty()
+        );
+        assert_eq!(
+            limits.check_withdrawal(&activity, Decimal::new(60, 0), &currency, at(11)),
+            [Limit::MaxWithdrawal, Limit::WithdrawalVolume]
+        );
+
+        assert_eq!(
+            limits.check_deposit(
+                &activity,
+                Decimal::new(95, 0),
+                Decimal::new(10, 0),
+                &currency,
+                at(11)
+            ),
+            [Limit::DepositCount, Limit::MaxBalance]
+        );
+
+        let config = LimitsConfig {
+            default: limits,
+            tiers: HashMap::from([("gold".to_string(), Limits::default())]),
+            client_tiers: HashMap::from([(ClientId(2), "gold".to_string())]),
+            ..Default::default()
+        };
+        assert!(config.limits(ClientId(1)).max_withdrawal.is_some());
+        assert!(config.limits(ClientId(2)).max_withdrawal.is_none());
+    }
+}
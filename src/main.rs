@@ -1,15 +1,38 @@
-use csv::{ReaderBuilder, Trim};
-use engine::TransactionsEngine;
-use std::fs::File;
+mod cli;
+
+use std::{
+    fs::File,
+    process::ExitCode,
+    time::{Duration, Instant},
+};
+
+use clap::Parser;
+use cli::{Cli, Command, Settings, normalize_args};
 use tracing::{debug, error, info};
 use tracing_subscriber::EnvFilter;
-use types::{TransactionRecord, TransactionType};
-
-mod client;
-mod engine;
-mod types;
+use transactions_engine::{
+    aging::AgingReport,
+    config::EngineConfig,
+    conversion::RateTable,
+    currency::Currency,
+    diff::{StateDiff, read_state, snapshot_state},
+    dry_run::DryRun,
+    engine::TransactionsEngine,
+    error::TransactionError,
+    input::TransactionReader,
+    metrics::Metrics,
+    output::OutputFormat,
+    reconcile::{breaks_json, read_expected, reconcile, write_breaks},
+    registry::Registry,
+    repl::Repl,
+    server::{Api, serve},
+    snapshot::Snapshot,
+    socket::listen,
+    statement::{Statement, StatementRange},
+    stats::Stats,
+};
 
-fn main() {
+fn main() -> ExitCode {
     // Logs disabled by default, use RUST_LOG to set the log level
     tracing_subscriber::fmt()
         .with_env_filter(
@@ -19,57 +42,333 @@ fn main() {
         )
         .init();
 
-    let args: Vec<String> = std::env::args().collect();
-    debug!("Binary arguments: {:?}", args);
+    let cli = Cli::parse_from(normalize_args(std::env::args_os().collect()));
+    debug!("Command line: {cli:?}");
 
-    // Check if input file was passed as an argument
-    if args.len() < 2 {
-        error!("Input file has not been provided");
-        return;
+    match run(cli) {
+        Ok(code) => code,
+        Err(err) => {
+            error!("{err}");
+            eprintln!("Error: {err}");
+            ExitCode::FAILURE
+        }
     }
+}
 
-    info!("Reading input from input file: {}", args[1]);
+fn run(cli: Cli) -> Result<ExitCode, String> {
+    let env = Settings::from_env(|name| std::env::var(name).ok())?;
+    let config = cli.settings.engine_config(&env)?;
 
-    let file = File::open(args[1].clone()).expect("Could not open input file");
+    match cli.command {
+        Command::Process { input } => {
+            let mut engine = build_engine(config.clone())?;
+            let mut metrics = Metrics::default();
+            process_file(&mut engine, &input, |transaction_type, result, latency| {
+                metrics.record(transaction_type, result, latency)
+            })?;
 
-    let mut reader = ReaderBuilder::new()
-        .flexible(true)
-        .has_headers(true)
-        .trim(Trim::All)
-        .from_reader(file);
+            // Write the current state
+            info!("Printing the current state");
+            engine.print_current_state();
+            write_outputs(&engine, &config, &metrics)?;
+        }
+        Command::Validate { input, snapshot } => {
+            // The engine is dropped by the dry run, nothing is written
+            let mut engine = build_engine(config.clone())?;
+            if let Some(path) = snapshot {
+                info!("Restoring snapshot from: {path}");
+                engine = engine.with_snapshot(Snapshot::from_file(&path)?);
+            }
 
-    let mut transactions_engine = TransactionsEngine::new();
+            info!("Validating input file: {input}");
+            let file =
+                File::open(&input).map_err(|err| format!("Could not open {input}: {err}"))?;
+            let dry_run = DryRun::run(engine, file);
 
-    // Process each transaction from the input file
-    for line in reader.deserialize() {
-        let record: TransactionRecord = match line {
-            Ok(record) => record,
-            Err(err) => {
-                error!("Could not deserialize line: {err:?}");
-                continue;
+            match config.output_format {
+                OutputFormat::Csv | OutputFormat::Markdown => dry_run.write(std::io::stdout())?,
+                OutputFormat::Json => println!("{:#}", dry_run.to_json()),
             }
-        };
 
-        debug!("Transaction record: {record:?}");
+            if !dry_run.is_clean() {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+        Command::Replay { snapshot, input } => {
+            info!("Restoring snapshot from: {snapshot}");
+            let mut engine =
+                build_engine(config.clone())?.with_snapshot(Snapshot::from_file(&snapshot)?);
+            let mut metrics = Metrics::default();
+            process_file(&mut engine, &input, |transaction_type, result, latency| {
+                metrics.record(transaction_type, result, latency)
+            })?;
 
-        let transaction = match TransactionType::from_transaction_record(record.clone()) {
-            Ok(transaction) => transaction,
-            Err(err) => {
-                error!(
-                    "Could not map transaction record {record:?} to a type: {err:?}. Skipping it."
+            engine.print_current_state();
+            write_outputs(&engine, &config, &metrics)?;
+        }
+        Command::Serve { address } => {
+            serve(Api::new(build_engine(config)?), &address)?;
+        }
+        Command::Listen { address } => {
+            listen(build_engine(config)?, &address)?;
+        }
+        Command::Repl { snapshot } => {
+            let mut engine = build_engine(config)?;
+            if let Some(path) = snapshot {
+                info!("Restoring snapshot from: {path}");
+                engine = engine.with_snapshot(Snapshot::from_file(&path)?);
+            }
+
+            let stdin = std::io::stdin();
+            Repl::new(engine)
+                .run(stdin.lock(), std::io::stdout())
+                .map_err(|err| format!("REPL stopped: {err}"))?;
+        }
+        Command::Diff {
+            before,
+            after,
+            transactions,
+        } => {
+            let is_snapshot = |path: &str| path.ends_with(".json");
+            let diff = if is_snapshot(&before) && is_snapshot(&after) {
+                let diff = StateDiff::between_snapshots(
+                    &Snapshot::from_file(&before)?,
+                    &Snapshot::from_file(&after)?,
                 );
+                if transactions {
+                    diff
+                } else {
+                    StateDiff {
+                        transactions: None,
+                        ..diff
+                    }
+                }
+            } else if transactions {
+                return Err("Transactions can only be listed between two snapshots".to_string());
+            } else {
+                let read = |path: &str| {
+                    let file =
+                        File::open(path).map_err(|err| format!("Could not open {path}: {err}"))?;
+                    read_state(file)
+                };
+                StateDiff::between(&read(&before)?, &read(&after)?)
+            };
+
+            match config.output_format {
+                OutputFormat::Csv | OutputFormat::Markdown => diff.write(std::io::stdout())?,
+                OutputFormat::Json => println!("{:#}", diff.to_json()),
+            }
+
+            if !diff.is_empty() {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+        Command::Reconcile {
+            state,
+            expected,
+            tolerance,
+            currency,
+        } => {
+            let state = if state.ends_with(".json") {
+                snapshot_state(&Snapshot::from_file(&state)?)
+            } else {
+                let file =
+                    File::open(&state).map_err(|err| format!("Could not open {state}: {err}"))?;
+                read_state(file)?
+            };
+
+            // Flags override the configuration
+            let reconciliation = config.reconciliation;
+            let tolerance = tolerance.unwrap_or(reconciliation.tolerance);
+            let currency = currency
+                .map(Currency::from)
+                .or(reconciliation.currency)
+                .unwrap_or(config.default_currency);
+
+            let file =
+                File::open(&expected).map_err(|err| format!("Could not open {expected}: {err}"))?;
+            let breaks = reconcile(&state, &read_expected(file, &currency)?, tolerance);
+
+            match config.output_format {
+                OutputFormat::Csv | OutputFormat::Markdown => {
+                    write_breaks(std::io::stdout(), &breaks)?
+                }
+                OutputFormat::Json => println!("{:#}", breaks_json(&breaks)),
+            }
+
+            if !breaks.is_empty() {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+        Command::Statement {
+            input,
+            client,
+            from_tx,
+            to_tx,
+            from,
+            to,
+            snapshot,
+        } => {
+            let mut engine = build_engine(config.clone())?;
+            if let Some(path) = snapshot {
+                info!("Restoring snapshot from: {path}");
+                engine = engine.with_snapshot(Snapshot::from_file(&path)?);
+            }
+
+            let file =
+                File::open(&input).map_err(|err| format!("Could not open {input}: {err}"))?;
+            let range = StatementRange {
+                from_tx,
+                to_tx,
+                from_time: from,
+                to_time: to,
+            };
+            Statement::generate(engine, file, client, &range)
+                .write(std::io::stdout(), config.output_format)?;
+        }
+        Command::Disputes { input, snapshot } => {
+            let mut engine = build_engine(config.clone())?;
+            if let Some(path) = snapshot {
+                info!("Restoring snapshot from: {path}");
+                engine = engine.with_snapshot(Snapshot::from_file(&path)?);
+            }
+            if let Some(input) = input {
+                process_file(&mut engine, &input, |_, _, _| {})?;
+            }
+
+            let report = AgingReport::new(&engine);
+            match config.output_format {
+                OutputFormat::Csv | OutputFormat::Markdown => report.write(std::io::stdout())?,
+                OutputFormat::Json => println!("{:#}", report.to_json()),
+            }
+        }
+        Command::Stats { input } => {
+            let mut engine = build_engine(config.clone())?;
+            let mut stats = Stats::default();
+            process_file(&mut engine, &input, |transaction_type, result, _| {
+                stats.record(transaction_type, result)
+            })?;
+
+            match config.output_format {
+                OutputFormat::Csv | OutputFormat::Markdown => stats
+                    .write(std::io::stdout(), &engine)
+                    .map_err(|err| format!("Could not write stats: {err}"))?,
+                OutputFormat::Json => println!("{:#}", stats.to_json(&engine)),
+            }
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Process each transaction of the input file, reporting the type of the transaction, `unknown`
+/// when it could not be read, whether it has been accepted and how long it took to read and process
+fn process_file(
+    engine: &mut TransactionsEngine,
+    path: &str,
+    mut report: impl FnMut(&'static str, &Result<(), TransactionError>, Duration),
+) -> Result<(), String> {
+    info!("Reading input from input file: {path}");
+    let file =
+        File::open(path).map_err(|err| format!("Could not open input file {path}: {err}"))?;
+
+    let mut started = Instant::now();
+    for line in TransactionReader::new(file) {
+        let input = match line {
+            Ok(input) => input,
+            Err(err) => {
+                error!("Could not read transaction: {err}. Skipping it.");
+                report("unknown", &Err(err.error.into()), started.elapsed());
+                started = Instant::now();
                 continue;
             }
         };
 
+        let transaction = input.transaction;
         info!("Processing transaction {transaction:?}");
 
-        if let Err(err) = transactions_engine.process_transaction(transaction.clone()) {
-            error!("Could not process transaction {transaction:?}: {err:?}")
+        let transaction_type = transaction.name();
+        let result = engine
+            .process_transaction_at(transaction.clone(), input.timestamp)
+            .map_err(TransactionError::from);
+        if let Err(err) = &result {
+            error!("Could not process transaction {transaction:?}: {err}")
         }
+        report(transaction_type, &result, started.elapsed());
+        started = Instant::now();
     }
 
-    // Write the current state
-    info!("Printing the current state");
-    transactions_engine.print_current_state();
+    Ok(())
+}
+
+/// Write the events, ledger, alerts, valuation, snapshot and metrics files of the configuration
+fn write_outputs(
+    engine: &TransactionsEngine,
+    config: &EngineConfig,
+    metrics: &Metrics,
+) -> Result<(), String> {
+    if let Some(path) = &config.events_output {
+        info!("Writing events to: {path}");
+        engine.write_events(create(path)?)?;
+    }
+
+    if let Some(path) = &config.ledger_output {
+        info!("Writing ledger to: {path}");
+        engine.write_ledger(create(path)?)?;
+    }
+
+    if let Some(path) = &config.alerts_output {
+        info!("Writing alerts to: {path}");
+        engine.write_alerts(create(path)?)?;
+    }
+
+    if let Some((currency, path)) = config
+        .reporting_currency
+        .as_ref()
+        .zip(config.valuation_output.as_ref())
+    {
+        info!("Writing valuation in {currency} to: {path}");
+        engine.write_valuation(create(path)?, currency)?;
+    }
+
+    if let Some(path) = &config.snapshot_output {
+        info!("Writing snapshot to: {path}");
+        engine.snapshot().to_file(path)?;
+    }
+
+    if let Some(path) = &config.metrics_output {
+        info!("Writing metrics to: {path}");
+        metrics.write(create(path)?, engine)?;
+    }
+
+    Ok(())
+}
+
+fn create(path: &str) -> Result<File, String> {
+    File::create(path).map_err(|err| format!("Could not create {path}: {err}"))
+}
+
+/// Build the engine with the conversion rates and the account registry of the configuration
+fn build_engine(config: EngineConfig) -> Result<TransactionsEngine, String> {
+    // Conversion rates are only needed to convert between currencies
+    let rates = match &config.conversion.rates {
+        Some(path) => {
+            info!("Reading conversion rates from: {path}");
+            RateTable::from_file(path)?
+        }
+        None => RateTable::default(),
+    };
+
+    // Accounts are opened by the transactions when there is no registry
+    let registry = match &config.registry.accounts {
+        Some(path) => {
+            info!("Reading account registry from: {path}");
+            Registry::from_file(path)?
+        }
+        None => Registry::default(),
+    };
+
+    Ok(TransactionsEngine::with_config(config)
+        .with_rates(rates)
+        .with_registry(registry))
 }
@@ -0,0 +1,288 @@
+use std::{collections::BTreeMap, fmt::Write as _, io::Write, time::Duration};
+
+use rust_decimal::Decimal;
+
+use crate::{currency::Currency, engine::TransactionsEngine, error::TransactionError};
+
+/// Upper bounds of the buckets of the latency histograms, in seconds
+const LATENCY_BUCKETS: [f64; 10] = [
+    0.000_005, 0.000_01, 0.000_025, 0.000_05, 0.000_1, 0.000_25, 0.000_5, 0.001, 0.005, 0.01,
+];
+
+/// Distribution of durations over the latency buckets.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Histogram {
+    /// Number of durations in each bucket, not cumulated
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    /// Sum of the durations, in seconds
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    pub fn observe(&mut self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        if let Some(bucket) = LATENCY_BUCKETS.iter().position(|bound| seconds <= *bound) {
+            self.buckets[bucket] += 1;
+        }
+        self.sum += seconds;
+        self.count += 1;
+    }
+}
+
+/// Counters and latencies of the processed transactions, exposed in the Prometheus text format
+/// along with gauges read from the engine.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Metrics {
+    /// Transactions processed, accepted or not, by type
+    processed: BTreeMap<&'static str, u64>,
+    /// Transactions rejected, by type and error
+    rejected: BTreeMap<(&'static str, &'static str), u64>,
+    /// Time taken to read and process the transactions, by type
+    latency: BTreeMap<&'static str, Histogram>,
+}
+
+impl Metrics {
+    /// Record a transaction of the given type, `unknown` when it could not be read, its result and
+    /// how long it took to handle
+    pub fn record(
+        &mut self,
+        transaction_type: &'static str,
+        result: &Result<(), TransactionError>,
+        latency: Duration,
+    ) {
+        *self.processed.entry(transaction_type).or_default() += 1;
+        if let Err(err) = result {
+            *self
+                .rejected
+                .entry((transaction_type, err.kind()))
+                .or_default() += 1;
+        }
+        self.latency
+            .entry(transaction_type)
+            .or_default()
+            .observe(latency);
+    }
+
+    pub fn processed(&self) -> u64 {
+        self.processed.values().sum()
+    }
+
+    pub fn rejected(&self) -> u64 {
+        self.rejected.values().sum()
+    }
+
+    /// Metrics in the Prometheus text exposition format, with the gauges of the engine
+    pub fn render(&self, engine: &TransactionsEngine) -> String {
+        let mut output = String::new();
+
+        header(
+            &mut output,
+            "engine_transactions_processed_total",
+            "counter",
+            "Transactions processed, accepted or not, by type",
+        );
+        for (transaction_type, count) in &self.processed {
+            let _ = writeln!(
+                output,
+                "engine_transactions_processed_total{{type=\"{transaction_type}\"}} {count}"
+            );
+        }
+
+        header(
+            &mut output,
+            "engine_transactions_rejected_total",
+            "counter",
+            "Transactions rejected, by type and error",
+        );
+        for ((transaction_type, error), count) in &self.rejected {
+            let _ = writeln!(
+                output,
+                "engine_transactions_rejected_total{{type=\"{transaction_type}\",error=\"{error}\"}} {count}"
+            );
+        }
+
+        let clients = engine.clients();
+        let open_disputes: usize = clients
+            .iter()
+            .map(|client| client.open_disputes().count())
+            .sum();
+        let mut held: BTreeMap<&Currency, Decimal> = BTreeMap::new();
+        for client in &clients {
+            for (currency, balance) in engine.balances(client) {
+                *held.entry(currency).or_default() += balance.held;
+            }
+        }
+
+        header(
+            &mut output,
+            "engine_clients",
+            "gauge",
+            "Clients with an account",
+        );
+        let _ = writeln!(output, "engine_clients {}", clients.len());
+        header(
+            &mut output,
+            "engine_locked_clients",
+            "gauge",
+            "Locked clients",
+        );
+        let _ = writeln!(
+            output,
+            "engine_locked_clients {}",
+            clients.iter().filter(|client| client.locked).count()
+        );
+        header(
+            &mut output,
+            "engine_open_disputes",
+            "gauge",
+            "Transactions currently disputed",
+        );
+        let _ = writeln!(output, "engine_open_disputes {open_disputes}");
+        header(
+            &mut output,
+            "engine_held_funds",
+            "gauge",
+            "Funds held by open disputes, by currency",
+        );
+        for (currency, held) in held {
+            let _ = writeln!(
+                output,
+                "engine_held_funds{{currency=\"{}\"}} {held}",
+                escape(&currency.to_string())
+            );
+        }
+
+        header(
+            &mut output,
+            "engine_processing_seconds",
+            "histogram",
+            "Time taken to read and process a transaction, by type",
+        );
+        for (transaction_type, histogram) in &self.latency {
+            let mut cumulated = 0;
+            for (bound, count) in LATENCY_BUCKETS.iter().zip(histogram.buckets) {
+                cumulated += count;
+                let _ = writeln!(
+                    output,
+                    "engine_processing_seconds_bucket{{type=\"{transaction_type}\",le=\"{bound}\"}} {cumulated}"
+                );
+            }
+            let _ = writeln!(
+                output,
+                "engine_processing_seconds_bucket{{type=\"{transaction_type}\",le=\"+Inf\"}} {}",
+                histogram.count
+            );
+            let _ = writeln!(
+                output,
+                "engine_processing_seconds_sum{{type=\"{transaction_type}\"}} {}",
+                histogram.sum
+            );
+            let _ = writeln!(
+                output,
+                "engine_processing_seconds_count{{type=\"{transaction_type}\"}} {}",
+                histogram.count
+            );
+        }
+
+        output
+    }
+
+    /// Write the metrics in the Prometheus text exposition format
+    pub fn write<W: Write>(
+        &self,
+        mut writer: W,
+        engine: &TransactionsEngine,
+    ) -> Result<(), String> {
+        writer
+            .write_all(self.render(engine).as_bytes())
+            .map_err(|err| format!("Could not write metrics: {err:?}"))
+    }
+}
+
+/// Help and type lines of a metric
+fn header(output: &mut String, name: &str, metric_type: &str, help: &str) {
+    let _ = writeln!(output, "# HELP {name} {help}");
+    let _ = writeln!(output, "# TYPE {name} {metric_type}");
+}
+
+/// Label value with its backslashes, quotes and newlines escaped
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use rust_decimal::Decimal;
+
+    use crate::{
+        engine::TransactionsEngine,
+        error::{EngineError, TransactionError},
+        ids::{ClientId, TxId},
+        types::TransactionType,
+    };
+
+    use super::Metrics;
+
+    #[test]
+    fn test_metrics() {
+        let mut engine = TransactionsEngine::new();
+        let mut metrics = Metrics::default();
+        for transaction in [
+            TransactionType::Deposit {
+                client: ClientId(1),
+                tx: TxId::from(1),
+                amount: Decimal::new(10, 0),
+                currency: None,
+            },
+            TransactionType::Withdrawal {
+                client: ClientId(1),
+                tx: TxId::from(2),
+                amount: Decimal::new(20, 0),
+                currency: None,
+            },
+            TransactionType::Dispute {
+                client: ClientId(1),
+                tx: TxId::from(1),
+                amount: None,
+            },
+        ] {
+            let transaction_type = transaction.name();
+            let result = engine
+                .process_transaction(transaction)
+                .map_err(TransactionError::from);
+            metrics.record(transaction_type, &result, Duration::from_micros(20));
+        }
+        metrics.record(
+            "withdrawal",
+            &Err(EngineError::InsufficientFunds.into()),
+            Duration::from_secs(1),
+        );
+
+        assert_eq!(metrics.processed(), 4);
+        assert_eq!(metrics.rejected(), 2);
+
+        let output = metrics.render(&engine);
+        for line in [
+            "# TYPE engine_transactions_processed_total counter\n",
+            "engine_transactions_processed_total{type=\"withdrawal\"} 2\n",
+            "engine_transactions_rejected_total{type=\"withdrawal\",error=\"insufficient_funds\"} 2\n",
+            "engine_clients 1\n",
+            "engine_locked_clients 0\n",
+            "engine_open_disputes 1\n",
+            "engine_held_funds{currency=\"\"} 10\n",
+            "engine_processing_seconds_bucket{type=\"deposit\",le=\"0.00001\"} 0\n",
+            "engine_processing_seconds_bucket{type=\"deposit\",le=\"0.000025\"} 1\n",
+            "engine_processing_seconds_bucket{type=\"withdrawal\",le=\"0.01\"} 1\n",
+            "engine_processing_seconds_bucket{type=\"withdrawal\",le=\"+Inf\"} 2\n",
+            "engine_processing_seconds_count{type=\"withdrawal\"} 2\n",
+        ] {
+            assert!(output.contains(line), "Missing {line:?} in {output}");
+        }
+    }
+}
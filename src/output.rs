@@ -0,0 +1,44 @@
+use serde::{Deserialize, Serialize};
+
+/// Format the state of the clients is written in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    /// One CSV row per client and currency
+    #[default]
+    Csv,
+    /// JSON array with one object per client and currency
+    Json,
+    /// Markdown table with one row per client and currency
+    Markdown,
+}
+
+/// Order the clients are written in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputOrder {
+    /// No particular order, the fastest
+    #[default]
+    None,
+    /// By increasing client id
+    Client,
+}
+
+/// Markdown table with the header and one row per record. Pipes in the fields are escaped.
+pub fn markdown_table<const N: usize>(
+    header: &[&str; N],
+    records: impl IntoIterator<Item = [String; N]>,
+) -> String {
+    let row = |fields: Vec<String>| format!("| {} |\n", fields.join(" | "));
+
+    let mut table = row(header.iter().map(|name| name.to_string()).collect());
+    table += &format!("|{}\n", "---|".repeat(N));
+    for record in records {
+        table += &row(record
+            .into_iter()
+            .map(|field| field.replace('|', "\\|"))
+            .collect());
+    }
+
+    table
+}
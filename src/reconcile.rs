@@ -0,0 +1,337 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::{Read, Write},
+    str::FromStr,
+};
+
+use csv::{ReaderBuilder, Trim, Writer};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use crate::{currency::Currency, diff::State, ids::ClientId};
+
+/// Settings of the reconciliation against an external balance file.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReconciliationConfig {
+    /// Largest difference between the expected and the actual total funds that is not a break
+    pub tolerance: Decimal,
+    /// Currency of the expected balances without one, the default currency when missing
+    pub currency: Option<Currency>,
+}
+
+/// Row of the external balance file. The balance is kept as text to be parsed as a decimal.
+#[derive(Debug, Deserialize)]
+struct ExpectedRecord {
+    client: ClientId,
+    #[serde(default)]
+    currency: Option<Currency>,
+    balance: String,
+    #[serde(default)]
+    locked: Option<bool>,
+}
+
+/// Total funds of a client in a currency, as expected by the external file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpectedBalance {
+    pub client: ClientId,
+    pub currency: Currency,
+    pub balance: Decimal,
+    /// Expected lock status, not checked when missing
+    pub locked: Option<bool>,
+}
+
+/// Type of difference between the expected and the actual state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakKind {
+    /// Expected balance without an account in the engine
+    MissingInternal,
+    /// Account of the engine without an expected balance
+    MissingExternal,
+    /// Total funds further from the expected balance than the tolerance
+    AmountMismatch,
+    /// Lock status different from the expected one
+    LockMismatch,
+}
+
+impl BreakKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::MissingInternal => "missing_internal",
+            Self::MissingExternal => "missing_external",
+            Self::AmountMismatch => "amount_mismatch",
+            Self::LockMismatch => "lock_mismatch",
+        }
+    }
+}
+
+/// Difference between the expected and the actual state of an account.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Break {
+    pub kind: BreakKind,
+    pub client: ClientId,
+    pub currency: Currency,
+    pub expected: Option<Decimal>,
+    pub actual: Option<Decimal>,
+    pub expected_locked: Option<bool>,
+    pub actual_locked: Option<bool>,
+}
+
+impl Break {
+    /// Actual minus expected total funds, when both are known
+    pub fn difference(&self) -> Option<Decimal> {
+        Some(self.actual? - self.expected?)
+    }
+}
+
+/// Read the expected balances from the CSV file, with the `client`, `balance` and optional
+/// `currency` and `locked` columns. Rows without a currency are in the given one.
+pub fn read_expected<R: Read>(
+    reader: R,
+    currency: &Currency,
+) -> Result<Vec<ExpectedBalance>, String> {
+    let mut seen = BTreeSet::new();
+
+    ReaderBuilder::new()
+        .trim(Trim::All)
+        .flexible(true)
+        .from_reader(reader)
+        .deserialize()
+        .zip(2..)
+        .map(|(record, line)| {
+            let record: ExpectedRecord =
+                record.map_err(|err| format!("Invalid expected balance on line {line}: {err}"))?;
+            let balance = Decimal::from_str(&record.balance).map_err(|err| {
+                format!("Invalid balance {:?} on line {line}: {err}", record.balance)
+            })?;
+            let currency = record.currency.unwrap_or_else(|| currency.clone());
+
+            if !seen.insert((record.client, currency.clone())) {
+                return Err(format!(
+                    "Duplicate balance of client {} in {currency:?} on line {line}",
+                    record.client
+                ));
+            }
+
+            Ok(ExpectedBalance {
+                client: record.client,
+                currency,
+                balance,
+                locked: record.locked,
+            })
+        })
+        .collect()
+}
+
+/// Breaks between the expected balances and the state of the engine, by client and currency. Only
+/// the accounts in the currencies of the expected balances are reconciled.
+pub fn reconcile(state: &State, expected: &[ExpectedBalance], tolerance: Decimal) -> Vec<Break> {
+    let currencies: BTreeSet<_> = expected.iter().map(|balance| &balance.currency).collect();
+    let expected: BTreeMap<_, _> = expected
+        .iter()
+        .map(|balance| ((balance.client, balance.currency.clone()), balance))
+        .collect();
+
+    let mut breaks = Vec::new();
+    for ((client, currency), balance) in &expected {
+        let new_break = |kind| Break {
+            kind,
+            client: *client,
+            currency: currency.clone(),
+            expected: Some(balance.balance),
+            actual: None,
+            expected_locked: balance.locked,
+            actual_locked: None,
+        };
+
+        let Some(account) = state.get(&(*client, currency.clone())) else {
+            breaks.push(new_break(BreakKind::MissingInternal));
+            continue;
+        };
+
+        if (account.total - balance.balance).abs() > tolerance {
+            breaks.push(Break {
+                actual: Some(account.total),
+                actual_locked: Some(account.locked),
+                ..new_break(BreakKind::AmountMismatch)
+            });
+        }
+        if balance
+            .locked
+            .is_some_and(|locked| locked != account.locked)
+        {
+            breaks.push(Break {
+                actual: Some(account.total),
+                actual_locked: Some(account.locked),
+                ..new_break(BreakKind::LockMismatch)
+            });
+        }
+    }
+
+    for ((client, currency), account) in state {
+        if currencies.contains(currency) && !expected.contains_key(&(*client, currency.clone())) {
+            breaks.push(Break {
+                kind: BreakKind::MissingExternal,
+                client: *client,
+                currency: currency.clone(),
+                expected: None,
+                actual: Some(account.total),
+                expected_locked: None,
+                actual_locked: Some(account.locked),
+            });
+        }
+    }
+
+    breaks.sort_by(|a, b| (a.client, &a.currency, a.kind).cmp(&(b.client, &b.currency, b.kind)));
+    breaks
+}
+
+/// Write the breaks as CSV
+pub fn write_breaks<W: Write>(writer: W, breaks: &[Break]) -> Result<(), String> {
+    let mut writer = Writer::from_writer(writer);
+    writer
+        .write_record([
+            "break",
+            "client",
+            "currency",
+            "expected",
+            "actual",
+            "difference",
+            "expected_locked",
+            "actual_locked",
+        ])
+        .map_err(|err| format!("Could not write record: {err:?}"))?;
+
+    for reconciliation_break in breaks {
+        writer
+            .write_record([
+                reconciliation_break.kind.name().to_string(),
+                reconciliation_break.client.to_string(),
+                reconciliation_break.currency.to_string(),
+                optional(reconciliation_break.expected),
+                optional(reconciliation_break.actual),
+                optional(reconciliation_break.difference()),
+                optional(reconciliation_break.expected_locked),
+                optional(reconciliation_break.actual_locked),
+            ])
+            .map_err(|err| format!("Could not write record: {err:?}"))?;
+    }
+
+    writer
+        .flush()
+        .map_err(|err| format!("Could not flush records: {err:?}"))
+}
+
+/// Field of the output, empty when the value is unknown
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+/// Breaks as a JSON array
+pub fn breaks_json(breaks: &[Break]) -> Value {
+    breaks
+        .iter()
+        .map(|reconciliation_break| {
+            json!({
+                "break": reconciliation_break.kind,
+                "client": reconciliation_break.client,
+                "currency": reconciliation_break.currency.to_string(),
+                "expected": reconciliation_break.expected.map(|amount| amount.to_string()),
+                "actual": reconciliation_break.actual.map(|amount| amount.to_string()),
+                "difference": reconciliation_break.difference().map(|amount| amount.to_string()),
+                "expected_locked": reconciliation_break.expected_locked,
+                "actual_locked": reconciliation_break.actual_locked,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+
+    use crate::{
+        currency::Currency,
+        diff::{AccountState, State},
+        ids::ClientId,
+    };
+
+    use super::{BreakKind, read_expected, reconcile, write_breaks};
+
+    #[test]
+    fn test_reconcile() {
+        let account = |total, locked| AccountState {
+            available: total,
+            held: Decimal::ZERO,
+            total,
+            locked,
+        };
+        let state = State::from([
+            (
+                (ClientId(1), Currency::from("USD")),
+                account(Decimal::new(1000, 2), false),
+            ),
+            (
+                (ClientId(2), Currency::from("USD")),
+                account(Decimal::new(500, 2), true),
+            ),
+            (
+                (ClientId(3), Currency::from("USD")),
+                account(Decimal::new(1, 0), false),
+            ),
+            (
+                (ClientId(3), Currency::from("EUR")),
+                account(Decimal::new(1, 0), false),
+            ),
+            (
+                (ClientId(4), Currency::from("USD")),
+                account(Decimal::new(2, 0), false),
+            ),
+        ]);
+
+        let expected = read_expected(
+            "client,balance,locked,currency\n\
+             1,10.004,,\n\
+             2,5.50,false,\n\
+             3,1,false,usd\n\
+             5,3,,\n"
+                .as_bytes(),
+            &Currency::from("USD"),
+        )
+        .expect("Invalid expected balances.");
+        assert_eq!(expected[2].currency, Currency::from("USD"));
+
+        let breaks = reconcile(&state, &expected, Decimal::new(1, 2));
+        let kinds: Vec<_> = breaks
+            .iter()
+            .map(|reconciliation_break| (reconciliation_break.client, reconciliation_break.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                (ClientId(2), BreakKind::AmountMismatch),
+                (ClientId(2), BreakKind::LockMismatch),
+                (ClientId(4), BreakKind::MissingExternal),
+                (ClientId(5), BreakKind::MissingInternal),
+            ]
+        );
+
+        let mut output = Vec::new();
+        write_breaks(&mut output, &breaks[..1]).expect("Could not write breaks.");
+        assert_eq!(
+            String::from_utf8(output).expect("Invalid output."),
+            "break,client,currency,expected,actual,difference,expected_locked,actual_locked\n\
+             amount_mismatch,2,USD,5.50,5.00,-0.50,false,true\n"
+        );
+
+        assert!(
+            read_expected(
+                "client,balance\n1,1\n1,2\n".as_bytes(),
+                &Currency::default()
+            )
+            .is_err()
+        );
+    }
+}
@@ -0,0 +1,155 @@
+use std::{collections::BTreeMap, fs::File, io::Read};
+
+use csv::{ReaderBuilder, Trim};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::{currency::Currency, ids::ClientId, limits::Limits};
+
+/// Settings of the account registry.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RegistryConfig {
+    /// CSV file with the registered accounts, loaded at startup
+    pub accounts: Option<String>,
+    /// Reject the transactions of the clients that are not registered
+    pub reject_unregistered: bool,
+}
+
+/// Status of a registered account.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountStatus {
+    #[default]
+    Active,
+    Locked,
+}
+
+/// Registered account, read from the registry file. Missing columns keep the engine settings.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RegisteredAccount {
+    /// Client id
+    pub client: ClientId,
+    /// Whether the account starts locked
+    #[serde(default)]
+    pub status: Option<AccountStatus>,
+    /// Tier of the client, used to find its limits
+    #[serde(default)]
+    pub tier: Option<String>,
+    /// Currency of the transactions of the client without one
+    #[serde(default)]
+    pub currency: Option<Currency>,
+    /// Maximum amount of a single withdrawal
+    #[serde(default)]
+    pub max_withdrawal: Option<Decimal>,
+    /// Maximum total funds
+    #[serde(default)]
+    pub max_balance: Option<Decimal>,
+    /// Amount the available funds can go below zero
+    #[serde(default)]
+    pub credit_limit: Option<Decimal>,
+}
+
+impl RegisteredAccount {
+    /// Whether the account sets any limit of its own
+    pub fn has_limits(&self) -> bool {
+        self.max_withdrawal.is_some() || self.max_balance.is_some() || self.credit_limit.is_some()
+    }
+
+    /// The given limits, with the limits set for the account replacing them
+    pub fn limits(&self, base: &Limits) -> Limits {
+        Limits {
+            max_withdrawal: self.max_withdrawal.or(base.max_withdrawal),
+            max_balance: self.max_balance.or(base.max_balance),
+            credit_limit: self.credit_limit.or(base.credit_limit),
+            ..base.clone()
+        }
+    }
+}
+
+/// Accounts known before any transaction is processed.
+#[derive(Debug, Default)]
+pub struct Registry {
+    accounts: BTreeMap<ClientId, RegisteredAccount>,
+}
+
+impl Registry {
+    /// Load the accounts from the given CSV file
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let file =
+            File::open(path).map_err(|err| format!("Could not open registry file: {err:?}"))?;
+
+        Self::from_reader(file)
+    }
+
+    /// Load the accounts from CSV with the `client` column and optional `status`, `tier`,
+    /// `currency`, `max_withdrawal`, `max_balance` and `credit_limit` columns
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self, String> {
+        let mut reader = ReaderBuilder::new()
+            .flexible(true)
+            .has_headers(true)
+            .trim(Trim::All)
+            .from_reader(reader);
+
+        let mut registry = Self::default();
+        for line in reader.deserialize() {
+            let account: RegisteredAccount =
+                line.map_err(|err| format!("Invalid registered account: {err:?}"))?;
+
+            if registry.accounts.contains_key(&account.client) {
+                return Err(format!("Client {} registered twice", account.client));
+            }
+            registry.insert(account);
+        }
+
+        Ok(registry)
+    }
+
+    pub fn insert(&mut self, account: RegisteredAccount) {
+        self.accounts.insert(account.client, account);
+    }
+
+    /// Registered accounts, by client id
+    pub fn accounts(&self) -> impl Iterator<Item = &RegisteredAccount> {
+        self.accounts.values()
+    }
+
+    pub fn contains(&self, client: ClientId) -> bool {
+        self.accounts.contains_key(&client)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+
+    use crate::{currency::Currency, ids::ClientId, limits::Limits};
+
+    use super::{AccountStatus, Registry};
+
+    #[test]
+    fn test_registry() {
+        let accounts = "client, status, tier, currency, max_withdrawal, max_balance, credit_limit\n\
+            1, active, gold, eur, , , 100\n\
+            2, locked\n";
+        let registry =
+            Registry::from_reader(accounts.as_bytes()).expect("Could not load registry.");
+
+        let accounts: Vec<_> = registry.accounts().collect();
+        assert_eq!(accounts.len(), 2);
+        assert_eq!(accounts[0].tier.as_deref(), Some("gold"));
+        assert_eq!(accounts[0].currency, Some(Currency::from("EUR")));
+        assert_eq!(accounts[1].status, Some(AccountStatus::Locked));
+        assert!(!registry.contains(ClientId(3)));
+
+        let limits = accounts[0].limits(&Limits {
+            max_withdrawal: Some(Decimal::new(50, 0)),
+            credit_limit: Some(Decimal::new(10, 0)),
+            ..Default::default()
+        });
+        assert_eq!(limits.max_withdrawal, Some(Decimal::new(50, 0)));
+        assert_eq!(limits.credit_limit, Some(Decimal::new(100, 0)));
+
+        assert!(Registry::from_reader("client\n1\n1\n".as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,370 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, BufRead, Write},
+    mem,
+};
+
+use csv::StringRecord;
+
+use crate::{
+    client::Client,
+    currency::Currency,
+    dispute::DisputeState,
+    engine::TransactionsEngine,
+    error::TransactionError,
+    ids::{ClientId, TxId},
+    input::{TransactionReader, parse_record, read_csv_line},
+    snapshot::Snapshot,
+};
+
+/// Columns of the transaction lines before any header line
+const DEFAULT_HEADER: [&str; 4] = ["type", "client", "tx", "amount"];
+
+/// Number of changes that can be undone, each of them keeping a snapshot of the whole engine
+const MAX_UNDO: usize = 20;
+
+const HELP: &str = "\
+<type>,<client>,<tx>,<amount>  process a transaction, CSV lines with a header starting with `type` set other columns
+show [client]                 state of a client, or of all the clients
+tx <id>                       status of a transaction
+disputes <client>             disputes of a client
+undo                          revert the last transaction, run or load, up to 20 times
+run <file>                    process the transactions of a CSV file
+load <file>                   restore a snapshot
+save <file>                   save a snapshot
+quit                          leave";
+
+/// Interactive session over an engine, for operations and debugging.
+pub struct Repl {
+    engine: TransactionsEngine,
+    /// State before each of the last changes, latest last
+    history: VecDeque<Snapshot>,
+    headers: StringRecord,
+}
+
+impl Repl {
+    pub fn new(engine: TransactionsEngine) -> Self {
+        Self {
+            engine,
+            history: VecDeque::new(),
+            headers: StringRecord::from(DEFAULT_HEADER.to_vec()),
+        }
+    }
+
+    pub fn engine(&self) -> &TransactionsEngine {
+        &self.engine
+    }
+
+    /// Read commands until the input ends or `quit`, writing a prompt before each of them
+    pub fn run<R: BufRead, W: Write>(&mut self, reader: R, mut writer: W) -> io::Result<()> {
+        write!(writer, "> ")?;
+        writer.flush()?;
+
+        for line in reader.lines() {
+            let line = line?;
+            if matches!(line.trim(), "quit" | "exit") {
+                break;
+            }
+
+            let output = self.execute(&line);
+            if !output.is_empty() {
+                writeln!(writer, "{output}")?;
+            }
+            write!(writer, "> ")?;
+            writer.flush()?;
+        }
+
+        Ok(())
+    }
+
+    /// Execute a command or process a transaction line, returning what to show
+    pub fn execute(&mut self, line: &str) -> String {
+        let line = line.trim();
+        let (command, argument) = line
+            .split_once(char::is_whitespace)
+            .map_or((line, ""), |(command, argument)| (command, argument.trim()));
+
+        let result = match (command, argument) {
+            ("", _) => Ok(String::new()),
+            ("help", _) => Ok(HELP.to_string()),
+            ("show", "") => self.show(self.engine.clients()),
+            ("show", client) => parse_client(client)
+                .and_then(|client| self.find_client(client))
+                .and_then(|client| self.show(vec![client])),
+            ("tx", tx) => self.transaction(tx),
+            ("disputes", client) => parse_client(client)
+                .and_then(|client| self.find_client(client))
+                .map(disputes),
+            ("undo", _) => self.undo(),
+            ("run", path) => self.run_file(path),
+            ("load", path) => self.load(path),
+            ("save", path) => self
+                .engine
+                .snapshot()
+                .to_file(path)
+                .map(|()| format!("Saved snapshot to {path}")),
+            _ => self.process(line),
+        };
+
+        result.unwrap_or_else(|err| format!("Error: {err}"))
+    }
+
+    /// Process a transaction line and show the state of its client
+    fn process(&mut self, line: &str) -> Result<String, String> {
+        let record = read_csv_line(line).map_err(|err| err.to_string())?;
+        if record.get(0) == Some("type") {
+            self.headers = record;
+            return Ok(format!(
+                "Columns: {}",
+                self.headers.iter().collect::<Vec<_>>().join(",")
+            ));
+        }
+
+        let (transaction, timestamp) =
+            parse_record(&record, &self.headers).map_err(|err| err.to_string())?;
+        let ids = transaction.ids();
+
+        let snapshot = self.engine.snapshot();
+        let outcome = match self.engine.process_transaction_at(transaction, timestamp) {
+            Ok(()) => {
+                self.remember(snapshot);
+                "Accepted".to_string()
+            }
+            Err(err) => {
+                let err = TransactionError::from(err);
+                format!("Rejected ({}): {err}", err.kind())
+            }
+        };
+
+        let state = match ids.and_then(|(client, _)| self.engine.client(client)) {
+            Some(client) => self.show(vec![client])?,
+            None => String::new(),
+        };
+
+        Ok(format!("{outcome}\n{state}").trim_end().to_string())
+    }
+
+    fn show(&self, clients: Vec<&Client>) -> Result<String, String> {
+        let mut output = Vec::new();
+        self.engine.write_state(&mut output, clients.into_iter())?;
+
+        String::from_utf8(output)
+            .map(|output| output.trim_end().to_string())
+            .map_err(|err| format!("Invalid state: {err:?}"))
+    }
+
+    fn find_client(&self, client: ClientId) -> Result<&Client, String> {
+        self.engine
+            .client(client)
+            .ok_or_else(|| format!("Client {client} not found"))
+    }
+
+    /// Status of the transaction in each account it has been processed for
+    fn transaction(&self, tx: &str) -> Result<String, String> {
+        let tx: TxId = tx
+            .parse()
+            .map_err(|err| format!("Invalid tx id {tx:?}: {err}"))?;
+
+        let lines: Vec<_> = self
+            .engine
+            .clients()
+            .into_iter()
+            .filter_map(|client| {
+                let transaction = client.processed_transactions.get(&tx)?;
+                let mut line = format!("client {}: {}", client.client, transaction.name());
+                if let Some((amount, currency)) = transaction.funds() {
+                    line += &format!(" of {amount}");
+                    if let Some(currency) =
+                        currency.filter(|currency| **currency != Currency::default())
+                    {
+                        line += &format!(" {currency}");
+                    }
+                }
+                if let Some(at) = client.processed_at(tx) {
+                    line += &format!(", processed at seq {}", at.seq);
+                    if let Some(timestamp) = at.timestamp {
+                        line += &format!(" (timestamp {timestamp})");
+                    }
+                }
+                if let Some(history) = client.dispute_history(tx) {
+                    line += &format!(", dispute {}", format_history(history));
+                }
+                if let Some(authorization) = client.authorization(tx) {
+                    line += &format!(", authorization {:?}", authorization.state());
+                }
+                Some(line)
+            })
+            .collect();
+
+        if lines.is_empty() {
+            return Err(format!("Transaction {tx} not found"));
+        }
+
+        Ok(lines.join("\n"))
+    }
+
+    /// Go back to the state before the last change
+    fn undo(&mut self) -> Result<String, String> {
+        let snapshot = self
+            .history
+            .pop_back()
+            .ok_or_else(|| "Nothing to undo".to_string())?;
+        self.restore(snapshot);
+
+        Ok("Undone".to_string())
+    }
+
+    /// Process the transactions of a CSV file, as a single change
+    fn run_file(&mut self, path: &str) -> Result<String, String> {
+        let file = File::open(path).map_err(|err| format!("Could not open {path}: {err:?}"))?;
+
+        let snapshot = self.engine.snapshot();
+        let (mut accepted, mut rejected) = (0, 0);
+        for input in TransactionReader::new(file) {
+            let result = input.map_err(|err| err.error.into()).and_then(|input| {
+                self.engine
+                    .process_transaction_at(input.transaction, input.timestamp)
+                    .map_err(TransactionError::from)
+            });
+            match result {
+                Ok(()) => accepted += 1,
+                Err(_) => rejected += 1,
+            }
+        }
+        self.remember(snapshot);
+
+        Ok(format!("{accepted} accepted, {rejected} rejected"))
+    }
+
+    fn load(&mut self, path: &str) -> Result<String, String> {
+        let snapshot = Snapshot::from_file(path)?;
+        self.remember(self.engine.snapshot());
+        self.restore(snapshot);
+
+        Ok(format!("Loaded snapshot from {path}"))
+    }
+
+    /// Keep the state before a change, forgetting the oldest one beyond `MAX_UNDO`
+    fn remember(&mut self, snapshot: Snapshot) {
+        if self.history.len() == MAX_UNDO {
+            self.history.pop_front();
+        }
+        self.history.push_back(snapshot);
+    }
+
+    fn restore(&mut self, snapshot: Snapshot) {
+        self.engine = mem::take(&mut self.engine).with_snapshot(snapshot);
+    }
+}
+
+fn parse_client(client: &str) -> Result<ClientId, String> {
+    client
+        .parse()
+        .map_err(|err| format!("Invalid client id {client:?}: {err}"))
+}
+
+/// Disputed transactions of the client, by transaction id
+fn disputes(client: &Client) -> String {
+    let mut txs: Vec<_> = client
+        .processed_transactions
+        .keys()
+        .filter(|tx| client.transaction_dispute(**tx).is_some())
+        .collect();
+    txs.sort();
+
+    if txs.is_empty() {
+        return format!("No disputes for client {}", client.client);
+    }
+
+    txs.into_iter()
+        .filter_map(|tx| {
+            let dispute = client.transaction_dispute(*tx)?;
+            Some(format!(
+                "tx {tx}: {:?}, held {}, charged back {}, {}",
+                dispute.state(),
+                dispute.held(),
+                dispute.charged_back(),
+                format_history(dispute.history())
+            ))
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn format_history(history: &[DisputeState]) -> String {
+    history
+        .iter()
+        .map(|state| format!("{state:?}"))
+        .collect::<Vec<_>>()
+        .join(" -> ")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::TransactionsEngine;
+
+    use super::{MAX_UNDO, Repl};
+
+    #[test]
+    fn test_repl() {
+        let mut repl = Repl::new(TransactionsEngine::new());
+
+        assert_eq!(
+            repl.execute("deposit, 1, 1, 10.0"),
+            "Accepted\n\
+             client,currency,available,held,reserved,total,locked,fees,credit_limit,credit_used,shortfall\n\
+             1,,10.0,0,0,10.0,false,0,0,0,0"
+        );
+        assert!(
+            repl.execute("withdrawal, 1, 2, 20.0")
+                .starts_with("Rejected (insufficient_funds): Insufficient funds")
+        );
+        assert!(repl.execute("dispute, 1, 1,").starts_with("Accepted"));
+
+        assert_eq!(
+            repl.execute("tx 1"),
+            "client 1: deposit of 10.0, processed at seq 1, dispute Settled -> Disputed"
+        );
+        assert_eq!(
+            repl.execute("disputes 1"),
+            "tx 1: Disputed, held 10.0, charged back 0, Settled -> Disputed"
+        );
+        assert_eq!(repl.execute("show 2"), "Error: Client 2 not found");
+
+        // undo the dispute, the rejected withdrawal didn't change anything
+        assert_eq!(repl.execute("undo"), "Undone");
+        assert_eq!(repl.execute("disputes 1"), "No disputes for client 1");
+        assert_eq!(repl.execute("undo"), "Undone");
+        assert_eq!(repl.execute("undo"), "Error: Nothing to undo");
+        assert_eq!(
+            repl.execute("show"),
+            "client,currency,available,held,reserved,total,locked,fees,credit_limit,credit_used,\
+             shortfall"
+        );
+
+        let mut output = Vec::new();
+        repl.run("deposit, 1, 1, 5.0\nquit\nshow\n".as_bytes(), &mut output)
+            .expect("Could not run the REPL.");
+        let output = String::from_utf8(output).expect("Invalid output.");
+        assert!(output.starts_with("> Accepted"));
+        assert!(output.ends_with("> "));
+
+        // only the last changes can be undone
+        let mut repl = Repl::new(TransactionsEngine::new());
+        for tx in 1..=MAX_UNDO + 5 {
+            assert!(
+                repl.execute(&format!("deposit, 1, {tx}, 1.0"))
+                    .starts_with("Accepted")
+            );
+        }
+        for _ in 0..MAX_UNDO {
+            assert_eq!(repl.execute("undo"), "Undone");
+        }
+        assert_eq!(repl.execute("undo"), "Error: Nothing to undo");
+        assert!(
+            repl.execute("show 1")
+                .ends_with("1,,5.0,0,0,5.0,false,0,0,0,0")
+        );
+    }
+}
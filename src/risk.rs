@@ -0,0 +1,388 @@
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    client::Client,
+    clock::{Instant, Window},
+    error::EngineError,
+    ids::{ClientId, TxId},
+    types::TransactionType,
+};
+
+/// Outcome of a risk rule for a transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    /// The transaction can be applied
+    Allow,
+    /// The transaction is rejected for the given reason
+    Reject(String),
+    /// The transaction is applied and an alert is raised for the given reason
+    Flag(String),
+    /// The transaction is applied, then the account is locked and an alert is raised for the
+    /// given reason
+    Lock(String),
+}
+
+/// Check run before a transaction is applied. Rules are `Send` so the engine can be shared between
+/// connections.
+pub trait RiskRule: Send {
+    /// Name of the rule, used in the alerts
+    fn name(&self) -> &str;
+
+    /// Check the transaction against the current state of the client it belongs to
+    fn check(&self, transaction: &TransactionType, client: &Client, now: Instant) -> Verdict;
+}
+
+/// Transaction flagged by a risk rule.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Alert {
+    /// When the transaction has been processed
+    pub at: Instant,
+    /// Name of the rule that raised the alert
+    pub rule: String,
+    /// Client id
+    pub client: ClientId,
+    /// Transaction id
+    pub tx: TxId,
+    /// Whether the account has been locked
+    pub locked: bool,
+    /// Reason given by the rule
+    pub reason: String,
+}
+
+impl Alert {
+    /// Header of the alerts output
+    pub const HEADER: [&str; 7] = [
+        "rule",
+        "client",
+        "tx",
+        "locked",
+        "reason",
+        "seq",
+        "timestamp",
+    ];
+
+    /// Fields of the alerts output, in the order of the header
+    pub fn to_record(&self) -> [String; 7] {
+        [
+            self.rule.clone(),
+            self.client.to_string(),
+            self.tx.to_string(),
+            self.locked.to_string(),
+            self.reason.clone(),
+            self.at.seq.to_string(),
+            self.at
+                .timestamp
+                .map(|timestamp| timestamp.to_string())
+                .unwrap_or_default(),
+        ]
+    }
+}
+
+/// Risk rules run in order. The first rule rejecting a transaction stops the evaluation.
+#[derive(Default)]
+pub struct RiskRules {
+    rules: Vec<Box<dyn RiskRule>>,
+}
+
+impl RiskRules {
+    /// Build the built-in rules listed in the configuration, in the same order
+    pub fn from_config(config: &[RuleConfig]) -> Self {
+        config.iter().cloned().fold(Self::default(), |rules, rule| {
+            rules.with_rule(rule.into_rule())
+        })
+    }
+
+    /// Run the given rule after the existing ones
+    pub fn with_rule(mut self, rule: Box<dyn RiskRule>) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Run the rules on the transaction. Returns the name and verdict of each rule that didn't
+    /// allow it, or the reason of the first rejection.
+    pub fn check(
+        &self,
+        transaction: &TransactionType,
+        client: &Client,
+        now: Instant,
+    ) -> Result<Vec<(String, Verdict)>, EngineError> {
+        let mut verdicts = Vec::new();
+
+        for rule in &self.rules {
+            match rule.check(transaction, client, now) {
+                Verdict::Allow => (),
+                Verdict::Reject(reason) => {
+                    return Err(EngineError::RejectedByRule {
+                        rule: rule.name().to_string(),
+                        reason,
+                    });
+                }
+                verdict => verdicts.push((rule.name().to_string(), verdict)),
+            }
+        }
+
+        Ok(verdicts)
+    }
+}
+
+/// Built-in rule and its settings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RuleConfig {
+    RapidWithdrawal(RapidWithdrawal),
+    DisputeRate(DisputeRate),
+    Structuring(Structuring),
+}
+
+impl RuleConfig {
+    pub fn into_rule(self) -> Box<dyn RiskRule> {
+        match self {
+            Self::RapidWithdrawal(rule) => Box::new(rule),
+            Self::DisputeRate(rule) => Box::new(rule),
+            Self::Structuring(rule) => Box::new(rule),
+        }
+    }
+}
+
+/// Flag withdrawals taking at least `percent` of what has been deposited within the window.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RapidWithdrawal {
+    pub window: Window,
+    pub percent: Decimal,
+}
+
+impl RiskRule for RapidWithdrawal {
+    fn name(&self) -> &str {
+        "rapid_withdrawal"
+    }
+
+    fn check(&self, transaction: &TransactionType, client: &Client, now: Instant) -> Verdict {
+        let TransactionType::Withdrawal {
+            amount, currency, ..
+        } = transaction
+        else {
+            return Verdict::Allow;
+        };
+
+        let currency = currency.clone().unwrap_or_default();
+        let deposited: Decimal = client
+            .activity()
+            .deposits(self.window, &currency, now)
+            .sum();
+
+        if deposited.is_zero() || *amount * Decimal::ONE_HUNDRED < deposited * self.percent {
+            return Verdict::Allow;
+        }
+
+        Verdict::Flag(format!(
+            "Withdrawal of {amount} shortly after deposits of {deposited}"
+        ))
+    }
+}
+
+/// Lock the accounts disputing more than `max_percent` of their deposits, once they made at least
+/// `min_deposits` deposits.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DisputeRate {
+    pub max_percent: Decimal,
+    pub min_deposits: usize,
+}
+
+impl RiskRule for DisputeRate {
+    fn name(&self) -> &str {
+        "dispute_rate"
+    }
+
+    fn check(&self, transaction: &TransactionType, client: &Client, _now: Instant) -> Verdict {
+        let TransactionType::Dispute { tx, .. } = transaction else {
+            return Verdict::Allow;
+        };
+
+        let deposits = client
+            .processed_transactions
+            .values()
+            .filter(|transaction| matches!(transaction, TransactionType::Deposit { .. }))
+            .count();
+        if deposits == 0 || deposits < self.min_deposits {
+            return Verdict::Allow;
+        }
+
+        let mut disputed = client.disputed_transactions();
+        if client
+            .transaction_dispute(*tx)
+            .is_none_or(|dispute| dispute.history().len() == 1)
+        {
+            disputed += 1;
+        }
+
+        let rate = Decimal::from(disputed) * Decimal::ONE_HUNDRED / Decimal::from(deposits);
+        if rate <= self.max_percent {
+            return Verdict::Allow;
+        }
+
+        Verdict::Lock(format!(
+            "{disputed} of {deposits} deposits disputed, above {}%",
+            self.max_percent
+        ))
+    }
+}
+
+/// Flag deposits of round amounts, multiple of `round_to`, once there are at least `count` of them
+/// within the window.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Structuring {
+    pub round_to: Decimal,
+    pub window: Window,
+    pub count: usize,
+}
+
+impl RiskRule for Structuring {
+    fn name(&self) -> &str {
+        "structuring"
+    }
+
+    fn check(&self, transaction: &TransactionType, client: &Client, now: Instant) -> Verdict {
+        let TransactionType::Deposit {
+            amount, currency, ..
+        } = transaction
+        else {
+            return Verdict::Allow;
+        };
+
+        let is_round =
+            |amount: Decimal| !self.round_to.is_zero() && (amount % self.round_to).is_zero();
+        if !is_round(*amount) {
+            return Verdict::Allow;
+        }
+
+        let currency = currency.clone().unwrap_or_default();
+        let count = client
+            .activity()
+            .deposits(self.window, &currency, now)
+            .filter(|amount| is_round(*amount))
+            .count()
+            + 1;
+        if count < self.count {
+            return Verdict::Allow;
+        }
+
+        Verdict::Flag(format!(
+            "{count} deposits of multiples of {}",
+            self.round_to
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+
+    use crate::{
+        client::Client,
+        clock::{Instant, Window},
+        currency::Currency,
+        error::EngineError,
+        ids::{ClientId, TxId},
+        types::TransactionType,
+    };
+
+    use super::{RapidWithdrawal, RiskRule, RiskRules, Structuring, Verdict};
+
+    struct RejectAll;
+
+    impl RiskRule for RejectAll {
+        fn name(&self) -> &str {
+            "reject_all"
+        }
+
+        fn check(
+            &self,
+            _transaction: &TransactionType,
+            _client: &Client,
+            _now: Instant,
+        ) -> Verdict {
+            Verdict::Reject("Nothing is allowed".to_string())
+        }
+    }
+
+    #[test]
+    fn test_rules() {
+        let mut client = Client::new(ClientId(1));
+        let deposit = |tx, amount| TransactionType::Deposit {
+            client: ClientId(1),
+            tx,
+            amount,
+            currency: None,
+        };
+        for seq in 1..=2 {
+            let tx = TxId::from(seq);
+            client
+                .deposit(Decimal::new(500, 0), tx, &Currency::default())
+                .expect("Deposit failed.");
+            client.record_transaction(
+                tx,
+                deposit(tx, Decimal::new(500, 0)),
+                Instant {
+                    seq,
+                    timestamp: None,
+                },
+            );
+        }
+
+        let rules = RiskRules::default()
+            .with_rule(Box::new(RapidWithdrawal {
+                window: Window::Transactions(5),
+                percent: Decimal::new(90, 0),
+            }))
+            .with_rule(Box::new(Structuring {
+                round_to: Decimal::new(100, 0),
+                window: Window::Transactions(5),
+                count: 3,
+            }));
+        let now = Instant {
+            seq: 3,
+            timestamp: None,
+        };
+
+        let withdrawal = TransactionType::Withdrawal {
+            client: ClientId(1),
+            tx: TxId::from(3),
+            amount: Decimal::new(950, 0),
+            currency: None,
+        };
+        assert_eq!(
+            rules.check(&withdrawal, &client, now),
+            Ok(vec![(
+                "rapid_withdrawal".to_string(),
+                Verdict::Flag("Withdrawal of 950 shortly after deposits of 1000".to_string())
+            )])
+        );
+
+        assert_eq!(
+            rules.check(
+                &deposit(TxId::from(3), Decimal::new(12345, 2)),
+                &client,
+                now
+            ),
+            Ok(vec![])
+        );
+        assert_eq!(
+            rules.check(&deposit(TxId::from(3), Decimal::new(300, 0)), &client, now),
+            Ok(vec![(
+                "structuring".to_string(),
+                Verdict::Flag("3 deposits of multiples of 100".to_string())
+            )])
+        );
+
+        // the rules after a rejection are not run
+        let rules = RiskRules::default().with_rule(Box::new(RejectAll));
+        assert_eq!(
+            rules.check(&withdrawal, &client, now),
+            Err(EngineError::RejectedByRule {
+                rule: "reject_all".to_string(),
+                reason: "Nothing is allowed".to_string(),
+            })
+        );
+    }
+}
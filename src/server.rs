@@ -0,0 +1,445 @@
+use std::time::Instant;
+
+use serde_json::{Value, json};
+use tiny_http::{Header, Server};
+use tracing::{error, info};
+
+use crate::{
+    client::Client,
+    engine::TransactionsEngine,
+    error::TransactionError,
+    ids::{ClientId, TxId},
+    input::{InputError, InputTransaction, LineError, TransactionReader, parse_json},
+    metrics::Metrics,
+};
+
+/// Response of the API, with a JSON body.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub body: Value,
+}
+
+impl Response {
+    fn ok(body: Value) -> Self {
+        Self { status: 200, body }
+    }
+
+    fn error(status: u16, kind: &str, reason: impl ToString) -> Self {
+        Self {
+            status,
+            body: json!({ "error": { "kind": kind, "reason": reason.to_string() } }),
+        }
+    }
+
+    fn not_found(reason: &str) -> Self {
+        Self::error(404, "not_found", reason)
+    }
+}
+
+/// Outcome of a transaction submitted to the API.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outcome {
+    /// Line of the CSV body, or position in the JSON body starting at 1
+    pub line: u64,
+    /// Client and transaction id, when the transaction could be read
+    pub ids: Option<(ClientId, TxId)>,
+    pub result: Result<(), TransactionError>,
+}
+
+impl Outcome {
+    pub fn to_json(&self) -> Value {
+        let mut outcome = json!({
+            "line": self.line,
+            "client": self.ids.map(|(client, _)| client.0),
+            "tx": self.ids.map(|(_, tx)| tx.to_string()),
+            "status": if self.result.is_ok() { "accepted" } else { "rejected" },
+        });
+        if let Err(err) = &self.result {
+            outcome["error"] = json!({ "kind": err.kind(), "reason": err.to_string() });
+        }
+
+        outcome
+    }
+}
+
+/// HTTP API over a transactions engine. Requests are handled one at a time, in the order they are
+/// received.
+///
+/// - `POST /transactions`: process a single transaction, a JSON object or a CSV row after its
+///   header
+/// - `POST /transactions/batch`: process a JSON array of transactions, or CSV rows
+/// - `GET /clients`: state of all the clients
+/// - `GET /clients/{client}`: state of a client
+/// - `GET /clients/{client}/transactions/{tx}`: status and dispute state of a transaction
+/// - `GET /metrics`: metrics in the Prometheus text format, only served over HTTP
+///
+/// The API can be used in-process, without a server, with [`Api::get`] and [`Api::post`].
+pub struct Api {
+    engine: TransactionsEngine,
+    metrics: Metrics,
+}
+
+impl Api {
+    pub fn new(engine: TransactionsEngine) -> Self {
+        Self {
+            engine,
+            metrics: Metrics::default(),
+        }
+    }
+
+    pub fn engine(&self) -> &TransactionsEngine {
+        &self.engine
+    }
+
+    /// Metrics of the transactions submitted so far, in the Prometheus text format
+    pub fn metrics(&self) -> String {
+        self.metrics.render(&self.engine)
+    }
+
+    pub fn get(&mut self, path: &str) -> Response {
+        self.handle("GET", path, None, "")
+    }
+
+    pub fn post(&mut self, path: &str, content_type: &str, body: &str) -> Response {
+        self.handle("POST", path, Some(content_type), body)
+    }
+
+    /// Route a request to its handler
+    pub fn handle(
+        &mut self,
+        method: &str,
+        path: &str,
+        content_type: Option<&str>,
+        body: &str,
+    ) -> Response {
+        let path = path.split('?').next().unwrap_or_default();
+        let segments: Vec<_> = path
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .collect();
+        let is_csv = content_type.is_some_and(|content_type| content_type.starts_with("text/csv"));
+
+        match (method, segments.as_slice()) {
+            ("POST", ["transactions"]) => self.post_transaction(body, is_csv),
+            ("POST", ["transactions", "batch"]) => self.post_batch(body, is_csv),
+            ("GET", ["clients"]) => Response::ok(json!({
+                "clients": self
+                    .engine
+                    .clients()
+                    .into_iter()
+                    .map(|client| self.client_json(client))
+                    .collect::<Vec<_>>(),
+            })),
+            ("GET", ["clients", client]) => match self.find_client(client) {
+                Ok(client) => Response::ok(self.client_json(client)),
+                Err(response) => response,
+            },
+            ("GET", ["clients", client, "transactions", tx]) => self.get_transaction(client, tx),
+            (_, ["transactions"] | ["transactions", "batch"] | ["clients", ..]) => {
+                Response::error(405, "method_not_allowed", "Method not allowed")
+            }
+            _ => Response::not_found("Unknown path"),
+        }
+    }
+
+    fn post_transaction(&mut self, body: &str, is_csv: bool) -> Response {
+        let mut inputs = match parse_body(body, is_csv) {
+            Ok(inputs) => inputs,
+            Err(response) => return response,
+        };
+        if inputs.len() != 1 {
+            return Response::error(
+                400,
+                "malformed",
+                format!("Expected one transaction, got {}", inputs.len()),
+            );
+        }
+
+        let outcome = self.process(inputs.remove(0));
+        let status = if outcome.result.is_ok() { 200 } else { 422 };
+
+        Response {
+            status,
+            body: outcome.to_json(),
+        }
+    }
+
+    fn post_batch(&mut self, body: &str, is_csv: bool) -> Response {
+        let inputs = match parse_body(body, is_csv) {
+            Ok(inputs) => inputs,
+            Err(response) => return response,
+        };
+
+        let outcomes: Vec<_> = inputs
+            .into_iter()
+            .map(|input| self.process(input))
+            .collect();
+        let accepted = outcomes
+            .iter()
+            .filter(|outcome| outcome.result.is_ok())
+            .count();
+
+        Response::ok(json!({
+            "accepted": accepted,
+            "rejected": outcomes.len() - accepted,
+            "outcomes": outcomes.iter().map(Outcome::to_json).collect::<Vec<_>>(),
+        }))
+    }
+
+    /// Process a transaction read from a request
+    fn process(&mut self, input: Result<InputTransaction, LineError>) -> Outcome {
+        let started = Instant::now();
+        let input = match input {
+            Ok(input) => input,
+            Err(err) => {
+                let result = Err(err.error.into());
+                self.metrics.record("unknown", &result, started.elapsed());
+                return Outcome {
+                    line: err.line,
+                    ids: None,
+                    result,
+                };
+            }
+        };
+
+        let ids = input.transaction.ids();
+        let transaction_type = input.transaction.name();
+        let result = self
+            .engine
+            .process_transaction_at(input.transaction, input.timestamp)
+            .map_err(TransactionError::from);
+        self.metrics
+            .record(transaction_type, &result, started.elapsed());
+
+        Outcome {
+            line: input.line,
+            ids,
+            result,
+        }
+    }
+
+    fn find_client(&self, client: &str) -> Result<&Client, Response> {
+        let client: ClientId = client.parse().map_err(|err| {
+            Response::error(400, "invalid_id", format!("Invalid client id: {err}"))
+        })?;
+
+        self.engine
+            .client(client)
+            .ok_or_else(|| Response::not_found("Client not found"))
+    }
+
+    fn get_transaction(&self, client: &str, tx: &str) -> Response {
+        let client = match self.find_client(client) {
+            Ok(client) => client,
+            Err(response) => return response,
+        };
+        let tx: TxId = match tx.parse() {
+            Ok(tx) => tx,
+            Err(err) => {
+                return Response::error(400, "invalid_id", format!("Invalid tx id: {err}"));
+            }
+        };
+
+        let Some(transaction) = client.processed_transactions.get(&tx) else {
+            return Response::not_found("Transaction not found");
+        };
+        let processed_at = client.processed_at(tx);
+
+        Response::ok(json!({
+            "client": client.client.0,
+            "tx": tx.to_string(),
+            "type": transaction.name(),
+            "seq": processed_at.map(|at| at.seq),
+            "timestamp": processed_at.and_then(|at| at.timestamp),
+            "dispute_state": client.dispute_state(tx),
+            "dispute_history": client.dispute_history(tx),
+            "authorization_state": client
+                .authorization(tx)
+                .map(|authorization| authorization.state()),
+        }))
+    }
+
+    fn client_json(&self, client: &Client) -> Value {
+        let balances: Vec<_> = self
+            .engine
+            .balances(client)
+            .into_iter()
+            .map(|(currency, balance)| {
+                json!({
+                    "currency": currency.to_string(),
+                    "available": balance.available.to_string(),
+                    "held": balance.held.to_string(),
+                    "reserved": balance.reserved.to_string(),
+                    "total": balance.total.to_string(),
+                    "fees": balance.fees.to_string(),
+                    "credit_used": balance.credit_used(client.credit_limit).to_string(),
+                    "shortfall": balance.shortfall(client.credit_limit).to_string(),
+                })
+            })
+            .collect();
+
+        json!({
+            "client": client.client.0,
+            "locked": client.locked,
+            "credit_limit": client.credit_limit.to_string(),
+            "balances": balances,
+        })
+    }
+}
+
+/// Read the transactions of a request body, CSV rows after a header or JSON
+fn parse_body(
+    body: &str,
+    is_csv: bool,
+) -> Result<Vec<Result<InputTransaction, LineError>>, Response> {
+    if is_csv {
+        return Ok(TransactionReader::new(body.as_bytes()).collect());
+    }
+
+    let value: Value = serde_json::from_str(body)
+        .map_err(|err| Response::error(400, "malformed", format!("Invalid JSON: {err}")))?;
+    let values = match value {
+        Value::Array(values) => values,
+        value => vec![value],
+    };
+
+    Ok(values
+        .iter()
+        .zip(1..)
+        .map(|(value, line)| {
+            parse_json(value)
+                .map(|(transaction, timestamp)| InputTransaction {
+                    line,
+                    transaction,
+                    timestamp,
+                })
+                .map_err(|error: InputError| LineError { line, error })
+        })
+        .collect())
+}
+
+/// Serve the API over HTTP on the given address, until the process is stopped
+pub fn serve(mut api: Api, address: &str) -> Result<(), String> {
+    let server = Server::http(address).map_err(|err| format!("Could not start server: {err:?}"))?;
+    info!("Listening on {address}");
+
+    let content_type = Header::from_bytes("Content-Type", "application/json")
+        .map_err(|_| "Invalid content type header".to_string())?;
+    let metrics_type = Header::from_bytes("Content-Type", "text/plain; version=0.0.4")
+        .map_err(|_| "Invalid content type header".to_string())?;
+
+    for mut request in server.incoming_requests() {
+        // Metrics are text, not JSON
+        if request.method().as_str() == "GET" && request.url().split('?').next() == Some("/metrics")
+        {
+            let http_response =
+                tiny_http::Response::from_string(api.metrics()).with_header(metrics_type.clone());
+            if let Err(err) = request.respond(http_response) {
+                error!("Could not send response: {err:?}");
+            }
+            continue;
+        }
+
+        let mut body = String::new();
+        let response = match request.as_reader().read_to_string(&mut body) {
+            Ok(_) => {
+                let request_type = request
+                    .headers()
+                    .iter()
+                    .find(|header| header.field.equiv("Content-Type"))
+                    .map(|header| header.value.to_string());
+
+                api.handle(
+                    request.method().as_str(),
+                    request.url(),
+                    request_type.as_deref(),
+                    &body,
+                )
+            }
+            Err(err) => Response::error(400, "malformed", format!("Could not read body: {err}")),
+        };
+
+        let http_response = tiny_http::Response::from_string(response.body.to_string())
+            .with_status_code(response.status)
+            .with_header(content_type.clone());
+        if let Err(err) = request.respond(http_response) {
+            error!("Could not send response: {err:?}");
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::engine::TransactionsEngine;
+
+    use super::Api;
+
+    #[test]
+    fn test_api() {
+        let mut api = Api::new(TransactionsEngine::new());
+
+        let response = api.post(
+            "/transactions",
+            "application/json",
+            r#"{"type": "deposit", "client": 1, "tx": 1, "amount": "10.0"}"#,
+        );
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body["status"], "accepted");
+
+        let response = api.post(
+            "/transactions/batch",
+            "text/csv",
+            "type,client,tx,amount\n\
+             withdrawal,1,2,20.0\n\
+             dispute,1,1,\n\
+             deposit,1,abc,1.0\n",
+        );
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body["accepted"], 1);
+        assert_eq!(response.body["rejected"], 2);
+        assert_eq!(
+            response.body["outcomes"][0]["error"],
+            json!({"kind": "insufficient_funds", "reason": "Insufficient funds"})
+        );
+        assert_eq!(response.body["outcomes"][1]["status"], "accepted");
+        assert_eq!(response.body["outcomes"][2]["line"], 4);
+        assert_eq!(response.body["outcomes"][2]["error"]["kind"], "invalid_id");
+
+        let response = api.post(
+            "/transactions",
+            "application/json",
+            r#"{"type": "resolve", "client": 2, "tx": 1}"#,
+        );
+        assert_eq!(response.status, 422);
+        assert_eq!(response.body["error"]["kind"], "client_not_found");
+
+        let response = api.get("/clients/1");
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body["balances"][0]["held"], "10.0");
+        assert_eq!(api.get("/clients").body["clients"][0]["client"], 1);
+        assert_eq!(api.get("/clients/2").status, 404);
+        assert!(api.metrics().contains(
+            "engine_transactions_rejected_total{type=\"unknown\",error=\"invalid_id\"} 1\n"
+        ));
+
+        let response = api.get("/clients/1/transactions/1");
+        assert_eq!(response.body["type"], "deposit");
+        assert_eq!(response.body["dispute_state"], "Disputed");
+        assert_eq!(
+            response.body["dispute_history"],
+            json!(["Settled", "Disputed"])
+        );
+        assert_eq!(api.get("/clients/1/transactions/2").status, 404);
+
+        assert_eq!(
+            api.post("/transactions", "application/json", "{").status,
+            400
+        );
+        assert_eq!(api.get("/transactions").status, 405);
+    }
+}
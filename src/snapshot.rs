@@ -0,0 +1,61 @@
+use std::{
+    fs::File,
+    io::{Read, Write},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    client::Client,
+    clock::Instant,
+    event::EngineEvent,
+    ids::{ClientId, TxId},
+    ledger::Ledger,
+    risk::Alert,
+};
+
+/// State of the engine at a point in time, enough to carry on processing transactions from there.
+/// The configuration, conversion rates and registry are not part of the snapshot.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Instant of the last processed transaction
+    pub now: Instant,
+    /// Day of the latest timestamped transaction
+    pub last_day: Option<u64>,
+    /// Client accounts, by client id
+    pub clients: Vec<Client>,
+    /// Open disputes that can expire
+    pub open_disputes: Vec<(Instant, ClientId, TxId)>,
+    /// Pending authorizations that can expire
+    pub open_authorizations: Vec<(Instant, ClientId, TxId)>,
+    pub events: Vec<EngineEvent>,
+    pub ledger: Ledger,
+    pub alerts: Vec<Alert>,
+}
+
+impl Snapshot {
+    /// Load a snapshot from the given JSON file
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let file =
+            File::open(path).map_err(|err| format!("Could not open snapshot file: {err:?}"))?;
+
+        Self::from_reader(file)
+    }
+
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self, String> {
+        serde_json::from_reader(reader).map_err(|err| format!("Invalid snapshot: {err:?}"))
+    }
+
+    /// Save the snapshot to the given JSON file
+    pub fn to_file(&self, path: &str) -> Result<(), String> {
+        let file =
+            File::create(path).map_err(|err| format!("Could not create snapshot file: {err:?}"))?;
+
+        self.write(file)
+    }
+
+    pub fn write<W: Write>(&self, writer: W) -> Result<(), String> {
+        serde_json::to_writer(writer, self)
+            .map_err(|err| format!("Could not write snapshot: {err:?}"))
+    }
+}
@@ -0,0 +1,256 @@
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::TcpListener,
+    str::FromStr,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    thread,
+};
+
+use csv::StringRecord;
+use serde_json::Value;
+use tracing::{error, info};
+
+use crate::{
+    engine::TransactionsEngine,
+    error::TransactionError,
+    input::{InputError, parse_json, parse_record, read_csv_line},
+};
+
+/// Columns of the CSV records sent before any header line
+const DEFAULT_HEADER: [&str; 4] = ["type", "client", "tx", "amount"];
+
+/// Command streaming the current state back over the connection
+const SNAPSHOT: &str = "SNAPSHOT";
+
+/// Line ending the output of a snapshot
+const END: &str = "END";
+
+/// Address the line protocol is served on, `tcp:<host>:<port>` or `unix:<path>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ListenAddress {
+    Tcp(String),
+    Unix(String),
+}
+
+impl FromStr for ListenAddress {
+    type Err = String;
+
+    fn from_str(address: &str) -> Result<Self, Self::Err> {
+        match address.split_once(':') {
+            Some(("tcp", address)) => Ok(Self::Tcp(address.to_string())),
+            Some(("unix", path)) => Ok(Self::Unix(path.to_string())),
+            _ => Err(format!(
+                "Invalid address {address:?}, expected tcp:<host>:<port> or unix:<path>"
+            )),
+        }
+    }
+}
+
+/// Accept connections on the given address until the process is stopped. Each connection is
+/// handled by its own thread, and all of them feed the same engine.
+pub fn listen(engine: TransactionsEngine, address: &ListenAddress) -> Result<(), String> {
+    let engine = Arc::new(Mutex::new(engine));
+
+    match address {
+        ListenAddress::Tcp(address) => {
+            let listener = TcpListener::bind(address)
+                .map_err(|err| format!("Could not listen on {address}: {err:?}"))?;
+            info!("Listening on tcp:{address}");
+
+            for stream in listener.incoming() {
+                match stream.and_then(|stream| Ok((stream.try_clone()?, stream))) {
+                    Ok((reader, writer)) => spawn_connection(&engine, reader, writer),
+                    Err(err) => error!("Could not accept connection: {err:?}"),
+                }
+            }
+        }
+        #[cfg(unix)]
+        ListenAddress::Unix(path) => {
+            let listener = std::os::unix::net::UnixListener::bind(path)
+                .map_err(|err| format!("Could not listen on {path}: {err:?}"))?;
+            info!("Listening on unix:{path}");
+
+            for stream in listener.incoming() {
+                match stream.and_then(|stream| Ok((stream.try_clone()?, stream))) {
+                    Ok((reader, writer)) => spawn_connection(&engine, reader, writer),
+                    Err(err) => error!("Could not accept connection: {err:?}"),
+                }
+            }
+        }
+        #[cfg(not(unix))]
+        ListenAddress::Unix(_) => {
+            return Err("Unix domain sockets are not supported on this platform".to_string());
+        }
+    }
+
+    Ok(())
+}
+
+fn spawn_connection<R, W>(engine: &Arc<Mutex<TransactionsEngine>>, reader: R, writer: W)
+where
+    R: io::Read + Send + 'static,
+    W: Write + Send + 'static,
+{
+    let engine = Arc::clone(engine);
+    thread::spawn(move || {
+        if let Err(err) = handle_connection(&engine, BufReader::new(reader), writer) {
+            error!("Connection closed: {err:?}");
+        }
+    });
+}
+
+/// Process the records of a connection, one per line, answering each of them with
+/// `ACK <line>` or `NACK <line> <error kind> <reason>`.
+///
+/// Lines starting with `{` are JSON records, other lines are CSV records with the columns
+/// `type,client,tx,amount` unless a header line starting with `type` sets other columns. The
+/// `SNAPSHOT` command writes the current state in the configured format and order, followed by an
+/// `END` line.
+pub fn handle_connection<R: BufRead, W: Write>(
+    engine: &Mutex<TransactionsEngine>,
+    reader: R,
+    mut writer: W,
+) -> io::Result<()> {
+    let mut headers = StringRecord::from(DEFAULT_HEADER.to_vec());
+
+    for (text, line) in reader.lines().zip(1u64..) {
+        let text = text?;
+        let text = text.trim();
+        if text.is_empty() {
+            continue;
+        }
+
+        if text.eq_ignore_ascii_case(SNAPSHOT) {
+            lock(engine)
+                .write_output(&mut writer)
+                .map_err(io::Error::other)?;
+            writeln!(writer, "{END}")?;
+            writer.flush()?;
+            continue;
+        }
+
+        let result = if text.starts_with('{') {
+            serde_json::from_str::<Value>(text)
+                .map_err(|err| InputError::Malformed(err.to_string()))
+                .and_then(|value| parse_json(&value))
+        } else {
+            match read_csv_line(text) {
+                Ok(record) if record.get(0) == Some("type") => {
+                    headers = record;
+                    writeln!(writer, "ACK {line}")?;
+                    writer.flush()?;
+                    continue;
+                }
+                Ok(record) => parse_record(&record, &headers),
+                Err(err) => Err(err),
+            }
+        };
+
+        let result = result
+            .map_err(TransactionError::from)
+            .and_then(|(transaction, timestamp)| {
+                lock(engine)
+                    .process_transaction_at(transaction, timestamp)
+                    .map_err(TransactionError::from)
+            });
+
+        match result {
+            Ok(()) => writeln!(writer, "ACK {line}")?,
+            Err(err) => writeln!(writer, "NACK {line} {} {err}", err.kind())?,
+        }
+        writer.flush()?;
+    }
+
+    Ok(())
+}
+
+/// Lock the engine, even if a connection panicked while holding it
+fn lock(engine: &Mutex<TransactionsEngine>) -> MutexGuard<'_, TransactionsEngine> {
+    engine.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use serde_json::Value;
+
+    use crate::{
+        config::EngineConfig,
+        engine::TransactionsEngine,
+        output::{OutputFormat, OutputOrder},
+    };
+
+    use super::{ListenAddress, handle_connection};
+
+    #[test]
+    fn test_connection() {
+        let engine = Mutex::new(TransactionsEngine::new());
+        let input = "deposit, 1, 1, 10.0\n\
+            withdrawal, 1, 2, 20.0\n\
+            \n\
+            {\"type\": \"withdrawal\", \"client\": 1, \"tx\": 3, \"amount\": \"2.5\"}\n\
+            type, client, tx, amount, currency\n\
+            deposit, 2, 4, 1.0, eur\n\
+            deposit, x\n\
+            SNAPSHOT\n";
+        let mut output = Vec::new();
+        handle_connection(&engine, input.as_bytes(), &mut output).expect("Connection failed.");
+
+        let output = String::from_utf8(output).expect("Invalid output.");
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(lines[0], "ACK 1");
+        assert_eq!(lines[1], "NACK 2 insufficient_funds Insufficient funds");
+        assert_eq!(lines[2], "ACK 4");
+        assert_eq!(lines[3], "ACK 5");
+        assert_eq!(lines[4], "ACK 6");
+        assert_eq!(
+            lines[5],
+            "NACK 7 invalid_id Invalid client id \"x\": malformed"
+        );
+
+        let mut snapshot = lines[7..lines.len() - 1].to_vec();
+        snapshot.sort();
+        assert_eq!(
+            snapshot,
+            [
+                "1,,7.5,0,0,7.5,false,0,0,0,0",
+                "2,EUR,1.0,0,0,1.0,false,0,0,0,0"
+            ]
+        );
+        assert_eq!(lines.last(), Some(&"END"));
+
+        // the state is written in the configured format and order
+        let engine = Mutex::new(TransactionsEngine::with_config(EngineConfig {
+            output_format: OutputFormat::Json,
+            output_order: OutputOrder::Client,
+            ..Default::default()
+        }));
+        let input = "deposit, 3, 1, 1.0\n\
+            deposit, 1, 2, 2.0\n\
+            deposit, 2, 3, 3.0\n\
+            SNAPSHOT\n";
+        let mut output = Vec::new();
+        handle_connection(&engine, input.as_bytes(), &mut output).expect("Connection failed.");
+
+        let output = String::from_utf8(output).expect("Invalid output.");
+        let state = output
+            .strip_prefix("ACK 1\nACK 2\nACK 3\n")
+            .and_then(|output| output.strip_suffix("END\n"))
+            .expect("Invalid output.");
+        let state: Value = serde_json::from_str(state).expect("Invalid JSON state.");
+        let clients: Vec<_> = state
+            .as_array()
+            .expect("Invalid JSON state.")
+            .iter()
+            .map(|client| client["client"].as_u64())
+            .collect();
+        assert_eq!(clients, [Some(1), Some(2), Some(3)]);
+
+        assert_eq!(
+            "unix:/tmp/engine.sock".parse(),
+            Ok(ListenAddress::Unix("/tmp/engine.sock".to_string()))
+        );
+        assert!("127.0.0.1:9000".parse::<ListenAddress>().is_err());
+    }
+}
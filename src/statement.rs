@@ -0,0 +1,444 @@
+use std::{
+    collections::BTreeMap,
+    io::{Read, Write},
+};
+
+use csv::Writer;
+use rust_decimal::Decimal;
+use serde_json::{Value, json};
+
+use crate::{
+    client::Client,
+    currency::Currency,
+    diff::AccountState,
+    engine::TransactionsEngine,
+    ids::{ClientId, TxId},
+    input::TransactionReader,
+    ledger::Account,
+    output::{OutputFormat, markdown_table},
+    types::TransactionType,
+};
+
+/// Part of the input covered by a statement. Transaction bounds are inclusive and refer to the
+/// transactions of the client, time bounds are inclusive unix timestamps.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StatementRange {
+    /// First transaction of the client in the statement
+    pub from_tx: Option<TxId>,
+    /// Last transaction of the client in the statement
+    pub to_tx: Option<TxId>,
+    pub from_time: Option<u64>,
+    pub to_time: Option<u64>,
+}
+
+impl StatementRange {
+    fn has_time(&self) -> bool {
+        self.from_time.is_some() || self.to_time.is_some()
+    }
+
+    fn contains_time(&self, timestamp: Option<u64>) -> bool {
+        if !self.has_time() {
+            return true;
+        }
+
+        timestamp.is_some_and(|timestamp| {
+            self.from_time.is_none_or(|from| timestamp >= from)
+                && self.to_time.is_none_or(|to| timestamp <= to)
+        })
+    }
+}
+
+/// Operation changing the funds of the client, with the funds after it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatementLine {
+    /// Line of the input
+    pub line: u64,
+    pub timestamp: Option<u64>,
+    /// Transaction id, missing for the changes made by the engine on its own
+    pub tx: Option<TxId>,
+    /// Transaction type, or what the engine did on its own such as `interest`
+    pub kind: String,
+    pub currency: Currency,
+    /// Amount of the transaction, or of the transaction it refers to
+    pub amount: Option<Decimal>,
+    /// Funds after the operation
+    pub balance: AccountState,
+}
+
+/// Operations of a client over a range of the input, between its opening and closing funds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Statement {
+    pub client: ClientId,
+    /// Funds before the first operation of the range, by currency
+    pub opening: BTreeMap<Currency, AccountState>,
+    pub lines: Vec<StatementLine>,
+    /// Funds after the last operation of the range, by currency
+    pub closing: BTreeMap<Currency, AccountState>,
+}
+
+impl Statement {
+    /// Process the CSV input on the engine and record every change of the funds of the client in
+    /// the range. Rejected transactions are not part of the statement, but the changes the engine
+    /// makes on its own while processing them, such as expired disputes, are.
+    pub fn generate<R: Read>(
+        mut engine: TransactionsEngine,
+        reader: R,
+        client: ClientId,
+        range: &StatementRange,
+    ) -> Self {
+        let mut opening = None;
+        let mut closing = None;
+        let mut lines = Vec::new();
+        // Waiting for the first transaction, or past the last one
+        let mut started = range.from_tx.is_none();
+        let mut ended = false;
+
+        for input in TransactionReader::new(reader).flatten() {
+            if ended {
+                break;
+            }
+
+            let own_tx = involves(&input.transaction, client);
+            if own_tx == range.from_tx && own_tx.is_some() {
+                started = true;
+            }
+            let in_range = started && range.contains_time(input.timestamp);
+            if in_range && opening.is_none() {
+                opening = Some(funds(&engine, client));
+            }
+            if own_tx.is_some() && own_tx == range.to_tx {
+                ended = true;
+            }
+
+            let before = funds(&engine, client);
+            let (events, ledger) = (engine.events().len(), engine.ledger().entries().len());
+            let transaction = input.transaction.clone();
+            let accepted = engine
+                .process_transaction_at(input.transaction, input.timestamp)
+                .is_ok();
+            let after = funds(&engine, client);
+
+            if !in_range {
+                continue;
+            }
+            closing = Some(after.clone());
+
+            let kind = match own_tx {
+                Some(_) if accepted => transaction.name().to_string(),
+                _ => adjustment(&engine, client, events, ledger),
+            };
+            for (currency, balance) in &after {
+                if before.get(currency) == Some(balance) {
+                    continue;
+                }
+
+                lines.push(StatementLine {
+                    line: input.line,
+                    timestamp: input.timestamp,
+                    tx: own_tx.filter(|_| accepted),
+                    kind: kind.clone(),
+                    currency: currency.clone(),
+                    amount: engine
+                        .client(client)
+                        .filter(|_| accepted)
+                        .and_then(|account| amount(account, &transaction)),
+                    balance: balance.clone(),
+                });
+            }
+        }
+
+        let opening = opening.unwrap_or_else(|| funds(&engine, client));
+        Self {
+            client,
+            closing: closing.unwrap_or_else(|| opening.clone()),
+            opening,
+            lines,
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        let balances = |balances: &BTreeMap<Currency, AccountState>| -> Vec<Value> {
+            balances
+                .iter()
+                .map(|(currency, balance)| {
+                    json!({
+                        "currency": currency.to_string(),
+                        "available": balance.available.to_string(),
+                        "held": balance.held.to_string(),
+                        "total": balance.total.to_string(),
+                        "locked": balance.locked,
+                    })
+                })
+                .collect()
+        };
+
+        json!({
+            "client": self.client,
+            "opening": balances(&self.opening),
+            "lines": self.lines.iter().map(|line| json!({
+                "line": line.line,
+                "timestamp": line.timestamp,
+                "tx": line.tx.map(|tx| tx.to_string()),
+                "type": line.kind,
+                "currency": line.currency.to_string(),
+                "amount": line.amount.map(|amount| amount.to_string()),
+                "available": line.balance.available.to_string(),
+                "held": line.balance.held.to_string(),
+                "total": line.balance.total.to_string(),
+            })).collect::<Vec<_>>(),
+            "closing": balances(&self.closing),
+        })
+    }
+
+    /// Write the statement in the given format. The CSV output has `opening` and `closing` rows
+    /// around the operations.
+    pub fn write<W: Write>(&self, mut writer: W, format: OutputFormat) -> Result<(), String> {
+        let result = match format {
+            OutputFormat::Csv => return self.write_csv(writer),
+            OutputFormat::Json => writeln!(writer, "{:#}", self.to_json()),
+            OutputFormat::Markdown => write!(writer, "{}", self.to_markdown()),
+        };
+
+        result.map_err(|err| format!("Could not write statement: {err:?}"))
+    }
+
+    fn write_csv<W: Write>(&self, writer: W) -> Result<(), String> {
+        let mut writer = Writer::from_writer(writer);
+        writer
+            .write_record(Self::HEADER)
+            .map_err(|err| format!("Could not write record: {err:?}"))?;
+
+        for record in self.records() {
+            writer
+                .write_record(record)
+                .map_err(|err| format!("Could not write record: {err:?}"))?;
+        }
+
+        writer
+            .flush()
+            .map_err(|err| format!("Could not flush records: {err:?}"))
+    }
+
+    fn to_markdown(&self) -> String {
+        format!(
+            "# Statement of client {}\n\n{}",
+            self.client,
+            markdown_table(&Self::HEADER, self.records())
+        )
+    }
+
+    /// Header of the CSV and Markdown statements
+    const HEADER: [&str; 9] = [
+        "line",
+        "timestamp",
+        "tx",
+        "type",
+        "currency",
+        "amount",
+        "available",
+        "held",
+        "total",
+    ];
+
+    /// Rows of the CSV and Markdown statements, in the order of the header
+    fn records(&self) -> Vec<[String; 9]> {
+        let balance_record = |kind: &str, currency: &Currency, balance: &AccountState| {
+            [
+                String::new(),
+                String::new(),
+                String::new(),
+                kind.to_string(),
+                currency.to_string(),
+                String::new(),
+                balance.available.to_string(),
+                balance.held.to_string(),
+                balance.total.to_string(),
+            ]
+        };
+
+        let opening = self
+            .opening
+            .iter()
+            .map(|(currency, balance)| balance_record("opening", currency, balance));
+        let lines = self.lines.iter().map(|line| {
+            [
+                line.line.to_string(),
+                line.timestamp
+                    .map(|timestamp| timestamp.to_string())
+                    .unwrap_or_default(),
+                line.tx.map(|tx| tx.to_string()).unwrap_or_default(),
+                line.kind.clone(),
+                line.currency.to_string(),
+                line.amount
+                    .map(|amount| amount.to_string())
+                    .unwrap_or_default(),
+                line.balance.available.to_string(),
+                line.balance.held.to_string(),
+                line.balance.total.to_string(),
+            ]
+        });
+        let closing = self
+            .closing
+            .iter()
+            .map(|(currency, balance)| balance_record("closing", currency, balance));
+
+        opening.chain(lines).chain(closing).collect()
+    }
+}
+
+/// Id of the transaction if it is one of the client's, including the transfers it receives
+fn involves(transaction: &TransactionType, client: ClientId) -> Option<TxId> {
+    let (source, tx) = transaction.ids()?;
+    let destination = match transaction {
+        TransactionType::Transfer { destination, .. } => Some(*destination),
+        _ => None,
+    };
+
+    (source == client || destination == Some(client)).then_some(tx)
+}
+
+/// Funds of the client in each currency
+fn funds(engine: &TransactionsEngine, client: ClientId) -> BTreeMap<Currency, AccountState> {
+    let Some(client) = engine.client(client) else {
+        return BTreeMap::new();
+    };
+
+    engine
+        .balances(client)
+        .into_iter()
+        .map(|(currency, balance)| {
+            (
+                currency.clone(),
+                AccountState {
+                    available: balance.available,
+                    held: balance.held,
+                    total: balance.total,
+                    locked: client.locked,
+                },
+            )
+        })
+        .collect()
+}
+
+/// Amount of the transaction, or of the transaction it refers to when it has none
+fn amount(client: &Client, transaction: &TransactionType) -> Option<Decimal> {
+    if let Some((amount, _)) = transaction.funds() {
+        return Some(amount);
+    }
+
+    match transaction {
+        TransactionType::Capture { tx, amount, .. }
+        | TransactionType::Refund { tx, amount, .. }
+        | TransactionType::Dispute { tx, amount, .. }
+        | TransactionType::Resolve { tx, amount, .. }
+        | TransactionType::Chargeback { tx, amount, .. } => {
+            amount.or_else(|| Some(client.processed_transactions.get(tx)?.funds()?.0))
+        }
+        _ => None,
+    }
+}
+
+/// What the engine did on its own for the client, from the events and ledger lines posted since
+/// the given positions
+fn adjustment(
+    engine: &TransactionsEngine,
+    client: ClientId,
+    events: usize,
+    ledger: usize,
+) -> String {
+    let event = engine.events()[events..]
+        .iter()
+        .find(|event| event.client == client)
+        .map(|event| event.kind.to_string());
+    let entry = || {
+        engine.ledger().entries()[ledger..]
+            .iter()
+            .find(|entry| entry.account == Account::Client(client))
+            .map(|entry| entry.kind.to_string())
+    };
+
+    event
+        .or_else(entry)
+        .unwrap_or_else(|| "adjustment".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+
+    use crate::{
+        engine::TransactionsEngine,
+        ids::{ClientId, TxId},
+        output::OutputFormat,
+    };
+
+    use super::{Statement, StatementRange};
+
+    #[test]
+    fn test_statement() {
+        let input = "type,client,tx,amount,timestamp\n\
+            deposit,1,1,10.0,100\n\
+            deposit,2,2,5.0,200\n\
+            withdrawal,1,3,2.5,300\n\
+            dispute,1,1,,400\n\
+            withdrawal,1,4,100.0,500\n\
+            resolve,1,1,,600\n\
+            deposit,1,5,1.0,700\n";
+
+        let statement = Statement::generate(
+            TransactionsEngine::new(),
+            input.as_bytes(),
+            ClientId(1),
+            &StatementRange::default(),
+        );
+        let types: Vec<_> = statement
+            .lines
+            .iter()
+            .map(|line| line.kind.as_str())
+            .collect();
+        assert_eq!(
+            types,
+            ["deposit", "withdrawal", "dispute", "resolve", "deposit"]
+        );
+        assert!(statement.opening.is_empty());
+        assert_eq!(statement.lines[2].amount, Some(Decimal::new(100, 1)));
+        assert_eq!(statement.lines[2].balance.held, Decimal::new(100, 1));
+
+        let range = StatementRange {
+            from_tx: Some(TxId::from(3)),
+            to_time: Some(600),
+            ..Default::default()
+        };
+        let statement = Statement::generate(
+            TransactionsEngine::new(),
+            input.as_bytes(),
+            ClientId(1),
+            &range,
+        );
+        let mut output = Vec::new();
+        statement
+            .write(&mut output, OutputFormat::Csv)
+            .expect("Could not write statement.");
+        assert_eq!(
+            String::from_utf8(output).expect("Invalid output."),
+            "line,timestamp,tx,type,currency,amount,available,held,total\n\
+             ,,,opening,,,10.0,0,10.0\n\
+             4,300,3,withdrawal,,2.5,7.5,0,7.5\n\
+             5,400,1,dispute,,10.0,-2.5,10.0,7.5\n\
+             7,600,1,resolve,,10.0,7.5,0.0,7.5\n\
+             ,,,closing,,,7.5,0.0,7.5\n"
+        );
+
+        let mut output = Vec::new();
+        statement
+            .write(&mut output, OutputFormat::Markdown)
+            .expect("Could not write statement.");
+        let output = String::from_utf8(output).expect("Invalid output.");
+        assert!(output.starts_with(
+            "# Statement of client 1\n\n\
+             | line | timestamp | tx | type | currency | amount | available | held | total |\n\
+             |---|---|---|---|---|---|---|---|---|\n"
+        ));
+        assert!(output.contains("| 4 | 300 | 3 | withdrawal |  | 2.5 | 7.5 | 0 | 7.5 |\n"));
+    }
+}
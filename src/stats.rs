@@ -0,0 +1,223 @@
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+};
+
+use rust_decimal::Decimal;
+use serde_json::{Value, json};
+
+use crate::{engine::TransactionsEngine, error::TransactionError};
+
+/// Summary of a run: the transactions processed and the resulting state of the clients.
+#[derive(Debug, Default)]
+pub struct Stats {
+    /// Number of accepted and rejected transactions of each type
+    by_type: BTreeMap<&'static str, (u64, u64)>,
+    /// Number of rejected transactions for each error kind
+    by_error: BTreeMap<&'static str, u64>,
+}
+
+/// Funds of all the clients in a currency.
+#[derive(Debug, Default, Clone, PartialEq)]
+struct Funds {
+    available: Decimal,
+    held: Decimal,
+    total: Decimal,
+}
+
+impl Stats {
+    /// Count a transaction of the given type, `unknown` for the ones that could not be read
+    pub fn record(
+        &mut self,
+        transaction_type: &'static str,
+        result: &Result<(), TransactionError>,
+    ) {
+        let (accepted, rejected) = self.by_type.entry(transaction_type).or_default();
+        match result {
+            Ok(()) => *accepted += 1,
+            Err(err) => {
+                *rejected += 1;
+                *self.by_error.entry(err.kind()).or_default() += 1;
+            }
+        }
+    }
+
+    pub fn accepted(&self) -> u64 {
+        self.by_type.values().map(|(accepted, _)| accepted).sum()
+    }
+
+    pub fn rejected(&self) -> u64 {
+        self.by_type.values().map(|(_, rejected)| rejected).sum()
+    }
+
+    /// Summary as JSON, with the state of the clients of the engine
+    pub fn to_json(&self, engine: &TransactionsEngine) -> Value {
+        let clients = engine.clients();
+        let funds: BTreeMap<_, _> = funds(engine)
+            .into_iter()
+            .map(|(currency, funds)| {
+                (
+                    currency,
+                    json!({
+                        "available": funds.available.to_string(),
+                        "held": funds.held.to_string(),
+                        "total": funds.total.to_string(),
+                    }),
+                )
+            })
+            .collect();
+
+        json!({
+            "transactions": self.accepted() + self.rejected(),
+            "accepted": self.accepted(),
+            "rejected": self.rejected(),
+            "by_type": self.by_type.iter().map(|(transaction_type, (accepted, rejected))| {
+                (transaction_type.to_string(), json!({ "accepted": accepted, "rejected": rejected }))
+            }).collect::<serde_json::Map<_, _>>(),
+            "by_error": self.by_error,
+            "clients": clients.len(),
+            "locked_clients": clients.iter().filter(|client| client.locked).count(),
+            "funds": funds,
+        })
+    }
+
+    /// Write the summary as text, with the state of the clients of the engine
+    pub fn write<W: Write>(&self, mut writer: W, engine: &TransactionsEngine) -> io::Result<()> {
+        writeln!(
+            writer,
+            "transactions: {} ({} accepted, {} rejected)",
+            self.accepted() + self.rejected(),
+            self.accepted(),
+            self.rejected()
+        )?;
+        for (transaction_type, (accepted, rejected)) in &self.by_type {
+            writeln!(
+                writer,
+                "  {transaction_type}: {accepted} accepted, {rejected} rejected"
+            )?;
+        }
+
+        if !self.by_error.is_empty() {
+            writeln!(writer, "rejections:")?;
+            for (kind, count) in &self.by_error {
+                writeln!(writer, "  {kind}: {count}")?;
+            }
+        }
+
+        let clients = engine.clients();
+        writeln!(
+            writer,
+            "clients: {} ({} locked)",
+            clients.len(),
+            clients.iter().filter(|client| client.locked).count()
+        )?;
+
+        let funds = funds(engine);
+        if !funds.is_empty() {
+            writeln!(writer, "funds:")?;
+            for (currency, funds) in funds {
+                let currency = if currency.is_empty() {
+                    "(default)"
+                } else {
+                    &currency
+                };
+                writeln!(
+                    writer,
+                    "  {currency}: available {}, held {}, total {}",
+                    funds.available, funds.held, funds.total
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Funds of all the clients of the engine, by currency
+fn funds(engine: &TransactionsEngine) -> BTreeMap<String, Funds> {
+    let mut funds: BTreeMap<String, Funds> = BTreeMap::new();
+    for client in engine.clients() {
+        for (currency, balance) in &client.balances {
+            let funds = funds.entry(currency.to_string()).or_default();
+            funds.available += balance.available;
+            funds.held += balance.held;
+            funds.total += balance.total;
+        }
+    }
+
+    funds
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+
+    use crate::{
+        engine::TransactionsEngine,
+        error::TransactionError,
+        ids::{ClientId, TxId},
+        types::TransactionType,
+    };
+
+    use super::Stats;
+
+    #[test]
+    fn test_stats() {
+        let mut engine = TransactionsEngine::new();
+        let mut stats = Stats::default();
+
+        let transactions = [
+            TransactionType::Deposit {
+                client: ClientId(1),
+                tx: TxId::from(1),
+                amount: Decimal::new(10, 0),
+                currency: None,
+            },
+            TransactionType::Deposit {
+                client: ClientId(2),
+                tx: TxId::from(2),
+                amount: Decimal::new(5, 0),
+                currency: None,
+            },
+            TransactionType::Withdrawal {
+                client: ClientId(1),
+                tx: TxId::from(3),
+                amount: Decimal::new(20, 0),
+                currency: None,
+            },
+            TransactionType::Dispute {
+                client: ClientId(2),
+                tx: TxId::from(2),
+                amount: None,
+            },
+        ];
+        for transaction in transactions {
+            let name = transaction.name();
+            let result = engine
+                .process_transaction(transaction)
+                .map_err(TransactionError::from);
+            stats.record(name, &result);
+        }
+
+        let mut output = Vec::new();
+        stats
+            .write(&mut output, &engine)
+            .expect("Could not write stats.");
+        assert_eq!(
+            String::from_utf8(output).expect("Invalid output."),
+            "transactions: 4 (3 accepted, 1 rejected)\n  \
+             deposit: 2 accepted, 0 rejected\n  \
+             dispute: 1 accepted, 0 rejected\n  \
+             withdrawal: 0 accepted, 1 rejected\n\
+             rejections:\n  \
+             insufficient_funds: 1\n\
+             clients: 2 (0 locked)\n\
+             funds:\n  \
+             (default): available 10, held 5, total 15\n"
+        );
+
+        let json = stats.to_json(&engine);
+        assert_eq!(json["by_type"]["withdrawal"]["rejected"], 1);
+        assert_eq!(json["funds"][""]["held"], "5");
+    }
+}
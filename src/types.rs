@@ -1,7 +1,16 @@
+use std::str::FromStr;
+
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 
-/// Decimal precision for amounts
+use crate::{
+    currency::Currency,
+    error::EngineError,
+    ids::{ClientId, IdError, TxId},
+    input::InputError,
+};
+
+/// Decimal precision for amounts of the currencies without a configured precision
 pub const DECIMAL_PRECISION: u32 = 4;
 
 /// Transaction information read from the input file.
@@ -10,74 +19,296 @@ pub struct TransactionRecord {
     /// Type of the transaction
     #[serde(rename = "type")]
     pub transaction_type: String,
-    /// Client id
-    pub client: u16,
-    /// Transaction id
-    pub tx: u32,
+    /// Client id, missing for control records
+    #[serde(default)]
+    pub client: Option<String>,
+    /// Transaction id, missing for control records
+    #[serde(default)]
+    pub tx: Option<String>,
     /// Amount of money
     pub amount: Option<String>,
+    /// Unix timestamp in seconds
+    #[serde(default)]
+    pub timestamp: Option<u64>,
+    /// Destination client id of a transfer
+    #[serde(default)]
+    pub destination: Option<String>,
+    /// Currency of the amount
+    #[serde(default)]
+    pub currency: Option<String>,
+    /// Currency the amount is converted to
+    #[serde(default)]
+    pub to_currency: Option<String>,
 }
 
 /// Transaction type.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum TransactionType {
     Deposit {
-        client: u16,
-        tx: u32,
+        client: ClientId,
+        tx: TxId,
         amount: Decimal,
+        currency: Option<Currency>,
     },
     Withdrawal {
-        client: u16,
-        tx: u32,
+        client: ClientId,
+        tx: TxId,
+        amount: Decimal,
+        currency: Option<Currency>,
+    },
+    /// Move funds from `client` to `destination`
+    Transfer {
+        client: ClientId,
+        destination: ClientId,
+        tx: TxId,
+        amount: Decimal,
+        currency: Option<Currency>,
+    },
+    /// Move funds of `client` from `currency` to `to_currency`
+    Convert {
+        client: ClientId,
+        tx: TxId,
+        amount: Decimal,
+        currency: Option<Currency>,
+        to_currency: Currency,
+    },
+    /// Reserve funds of `client` until they are captured or released
+    Authorize {
+        client: ClientId,
+        tx: TxId,
         amount: Decimal,
+        currency: Option<Currency>,
+    },
+    /// Settle the funds reserved by the authorization `tx`
+    Capture {
+        client: ClientId,
+        tx: TxId,
+        amount: Option<Decimal>,
+    },
+    /// Release the funds reserved by the authorization `tx`
+    Void {
+        client: ClientId,
+        tx: TxId,
+    },
+    /// Reverse all or part of the deposit or withdrawal `tx` without locking the account
+    Refund {
+        client: ClientId,
+        tx: TxId,
+        amount: Option<Decimal>,
     },
     Dispute {
-        client: u16,
-        tx: u32,
+        client: ClientId,
+        tx: TxId,
+        amount: Option<Decimal>,
     },
     Resolve {
-        client: u16,
-        tx: u32,
+        client: ClientId,
+        tx: TxId,
+        amount: Option<Decimal>,
     },
     Chargeback {
-        client: u16,
-        tx: u32,
+        client: ClientId,
+        tx: TxId,
+        amount: Option<Decimal>,
     },
+    /// Control record accruing a day of interest for all the clients
+    Accrue,
     Unknown,
 }
 
 impl TransactionType {
-    pub fn from_transaction_record(record: TransactionRecord) -> Result<Self, String> {
-        let client = record.client;
-        let tx = record.tx;
+    pub fn from_transaction_record(record: TransactionRecord) -> Result<Self, InputError> {
+        if record.transaction_type == "accrue" {
+            return Ok(Self::Accrue);
+        }
+
+        let client = parse_id("client", record.client)?;
+        let tx = parse_id("tx", record.tx)?;
+        let currency = record.currency.map(Currency::from);
 
         let transaction = match record.transaction_type.as_str() {
             "deposit" => Self::Deposit {
                 client,
                 tx,
-                amount: parse_with_decimal_precision(record.amount.unwrap())?,
+                amount: parse_required_amount(record.amount)?,
+                currency,
             },
             "withdrawal" => Self::Withdrawal {
                 client,
                 tx,
-                amount: parse_with_decimal_precision(record.amount.unwrap())?,
+                amount: parse_required_amount(record.amount)?,
+                currency,
+            },
+            "transfer" => Self::Transfer {
+                client,
+                destination: parse_id("destination", record.destination)?,
+                tx,
+                amount: parse_required_amount(record.amount)?,
+                currency,
+            },
+            "convert" => Self::Convert {
+                client,
+                tx,
+                amount: parse_required_amount(record.amount)?,
+                currency,
+                to_currency: Currency::from(
+                    record
+                        .to_currency
+                        .ok_or(InputError::MissingField("to_currency"))?,
+                ),
+            },
+            "authorize" => Self::Authorize {
+                client,
+                tx,
+                amount: parse_required_amount(record.amount)?,
+                currency,
+            },
+            "capture" => Self::Capture {
+                client,
+                tx,
+                amount: parse_optional_amount(record.amount)?,
+            },
+            "void" => Self::Void { client, tx },
+            "refund" => Self::Refund {
+                client,
+                tx,
+                amount: parse_optional_amount(record.amount)?,
+            },
+            "dispute" => Self::Dispute {
+                client,
+                tx,
+                amount: parse_optional_amount(record.amount)?,
+            },
+            "resolve" => Self::Resolve {
+                client,
+                tx,
+                amount: parse_optional_amount(record.amount)?,
+            },
+            "chargeback" => Self::Chargeback {
+                client,
+                tx,
+                amount: parse_optional_amount(record.amount)?,
             },
-            "dispute" => Self::Dispute { client, tx },
-            "resolve" => Self::Resolve { client, tx },
-            "chargeback" => Self::Chargeback { client, tx },
             _ => Self::Unknown,
         };
 
         Ok(transaction)
     }
+
+    /// Name of the transaction type, as in the input
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Deposit { .. } => "deposit",
+            Self::Withdrawal { .. } => "withdrawal",
+            Self::Transfer { .. } => "transfer",
+            Self::Convert { .. } => "convert",
+            Self::Authorize { .. } => "authorize",
+            Self::Capture { .. } => "capture",
+            Self::Void { .. } => "void",
+            Self::Refund { .. } => "refund",
+            Self::Dispute { .. } => "dispute",
+            Self::Resolve { .. } => "resolve",
+            Self::Chargeback { .. } => "chargeback",
+            Self::Accrue => "accrue",
+            Self::Unknown => "unknown",
+        }
+    }
+
+    /// Use the given currency for the transactions moving funds without one
+    pub fn with_default_currency(mut self, default: &Currency) -> Self {
+        if let Self::Deposit { currency, .. }
+        | Self::Withdrawal { currency, .. }
+        | Self::Transfer { currency, .. }
+        | Self::Convert { currency, .. }
+        | Self::Authorize { currency, .. } = &mut self
+            && currency.is_none()
+        {
+            *currency = Some(default.clone());
+        }
+
+        self
+    }
+
+    /// Client and transaction id, the client being the source of a transfer
+    pub fn ids(&self) -> Option<(ClientId, TxId)> {
+        match self {
+            Self::Deposit { client, tx, .. }
+            | Self::Withdrawal { client, tx, .. }
+            | Self::Transfer { client, tx, .. }
+            | Self::Convert { client, tx, .. }
+            | Self::Authorize { client, tx, .. }
+            | Self::Capture { client, tx, .. }
+            | Self::Void { client, tx }
+            | Self::Refund { client, tx, .. }
+            | Self::Dispute { client, tx, .. }
+            | Self::Resolve { client, tx, .. }
+            | Self::Chargeback { client, tx, .. } => Some((*client, *tx)),
+            Self::Accrue | Self::Unknown => None,
+        }
+    }
+
+    /// Amount and currency moved by a deposit, withdrawal, transfer, conversion or authorization
+    pub fn funds(&self) -> Option<(Decimal, Option<&Currency>)> {
+        match self {
+            Self::Deposit {
+                amount, currency, ..
+            }
+            | Self::Withdrawal {
+                amount, currency, ..
+            }
+            | Self::Transfer {
+                amount, currency, ..
+            }
+            | Self::Convert {
+                amount, currency, ..
+            }
+            | Self::Authorize {
+                amount, currency, ..
+            } => Some((*amount, currency.as_ref())),
+            _ => None,
+        }
+    }
+}
+
+/// Parse an amount, keeping all its decimals
+pub fn parse_amount(amount: String) -> Result<Decimal, String> {
+    Decimal::from_str_exact(&amount).map_err(|err| format!("Invalid decimal {err:?}"))
 }
 
 /// Ensure the correct precision
-pub fn parse_with_decimal_precision(amount: String) -> Result<Decimal, String> {
-    let decimal =
-        Decimal::from_str_exact(&amount).map_err(|err| format!("Invalid decimal {err:?}"))?;
-    if decimal.scale() > DECIMAL_PRECISION {
-        return Err("Invalid decimal precision".to_string());
+pub fn check_precision(amount: Decimal, precision: u32) -> Result<(), EngineError> {
+    if amount.scale() > precision {
+        return Err(EngineError::InvalidPrecision);
     }
-    Ok(decimal)
+    Ok(())
+}
+
+/// Parse the amount of a dispute, resolve, chargeback, capture or refund. A missing amount refers
+/// to the whole amount of the transaction.
+fn parse_optional_amount(amount: Option<String>) -> Result<Option<Decimal>, InputError> {
+    amount
+        .map(parse_amount)
+        .transpose()
+        .map_err(InputError::InvalidAmount)
+}
+
+/// Parse the amount of a deposit, withdrawal, transfer, conversion or authorization
+fn parse_required_amount(amount: Option<String>) -> Result<Decimal, InputError> {
+    let amount = amount.ok_or(InputError::MissingField("amount"))?;
+
+    parse_amount(amount).map_err(InputError::InvalidAmount)
+}
+
+/// Parse a client or transaction id of the record
+fn parse_id<T: FromStr<Err = IdError>>(
+    field: &'static str,
+    id: Option<String>,
+) -> Result<T, InputError> {
+    let id = id.ok_or(InputError::MissingField(field))?;
+
+    id.parse().map_err(|error| InputError::InvalidId {
+        field,
+        value: id,
+        error,
+    })
 }